@@ -0,0 +1,110 @@
+//! Archive of the raw output of every analyzed binary.
+//!
+//! Each runner invocation gets its own directory `<binary>-<timestamp>-<pid>`
+//! inside of the archive directory (`valgrind/` inside of the target directory
+//! by default). It contains the raw XML output of valgrind (`valgrind.xml` and
+//! `valgrind-<n>.xml` for the n-th traced child process) or
+//! the output file of a profiling tool (e.g. `massif.out`), the captured stderr
+//! (`stderr.txt`) and the final report (`report.json`), so that a run can be
+//! analyzed again later, e.g. via `cargo valgrind report`.
+//!
+//! All runs of a single `cargo valgrind` invocation are listed in the index file
+//! ([`INDEX`]) inside of the archive directory, one JSON object per line.
+use crate::output::Report;
+use serde_json::json;
+use std::ffi::OsStr;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write as _};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The name of the index file listing all runs of a `cargo valgrind` call.
+pub const INDEX: &str = "index.jsonl";
+
+/// The archive directory of a single run.
+#[derive(Debug, Clone)]
+pub struct Archive {
+    /// The directory containing the files of this run.
+    directory: PathBuf,
+}
+impl Archive {
+    /// Start a new (empty) index inside of the archive directory `root`.
+    ///
+    /// # Errors
+    /// Any I/O error during creating the directory or the index is returned.
+    pub fn start_index(root: &Path) -> io::Result<()> {
+        fs::create_dir_all(root)?;
+        fs::write(root.join(INDEX), "")
+    }
+
+    /// Create the archive directory for a new run of `binary` inside of the
+    /// archive directory `root`.
+    ///
+    /// # Errors
+    /// Any I/O error during creating the directory is returned.
+    pub fn create(root: &Path, binary: &OsStr) -> io::Result<Self> {
+        let name = Path::new(binary)
+            .file_name()
+            .map_or_else(|| "unknown".into(), OsStr::to_string_lossy);
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let directory = root.join(format!("{name}-{time}-{}", process::id()));
+        fs::create_dir_all(&directory)?;
+        Ok(Self { directory })
+    }
+
+    /// Create the file for the raw XML output of valgrind, which is written
+    /// while it is received.
+    ///
+    /// The `index` is the number of the XML connection, which is non-zero for
+    /// traced child processes only. Those are stored as `valgrind-<index>.xml`.
+    ///
+    /// # Errors
+    /// Any I/O error during creating the file is returned.
+    pub fn create_xml(&self, index: usize) -> io::Result<File> {
+        let name = match index {
+            0 => String::from("valgrind.xml"),
+            index => format!("valgrind-{index}.xml"),
+        };
+        File::create(self.directory.join(name))
+    }
+
+    /// Store the output file of a profiling tool as `<tool>.out`.
+    ///
+    /// # Errors
+    /// Any I/O error during writing the file is returned.
+    pub fn store_profile(&self, tool: &str, profile: &[u8]) -> io::Result<()> {
+        fs::write(self.directory.join(format!("{tool}.out")), profile)
+    }
+
+    /// Store the captured stderr of valgrind and the program under test.
+    ///
+    /// # Errors
+    /// Any I/O error during writing the file is returned.
+    pub fn store_stderr(&self, stderr: &[u8]) -> io::Result<()> {
+        fs::write(self.directory.join("stderr.txt"), stderr)
+    }
+
+    /// Store the final report of the run and add the run to the index.
+    ///
+    /// # Errors
+    /// Any I/O error during writing the files is returned.
+    pub fn store_report(&self, report: &Report) -> io::Result<()> {
+        let json = serde_json::to_vec(report).map_err(io::Error::other)?;
+        fs::write(self.directory.join("report.json"), json)?;
+
+        let entry = json!({
+            "binary": report.binary,
+            "directory": self.directory,
+            "exit_code": report.exit_code,
+            "signal": report.signal,
+            "errors": report.errors.len(),
+        });
+        let index = self.directory.with_file_name(INDEX);
+        let mut index = OpenOptions::new().create(true).append(true).open(index)?;
+        writeln!(index, "{entry}")
+    }
+}
@@ -0,0 +1,178 @@
+//! Support for a baseline of known errors.
+//!
+//! Projects with known errors, that cannot be fixed right now, can record them
+//! in a baseline file. Afterwards only errors not contained in the baseline are
+//! treated as failures. Baseline errors, that don't occur anymore, are reported
+//! as fixed.
+//!
+//! Errors are identified by their fingerprint: the error kind followed by the
+//! function names and source files of the main stack trace. Instruction
+//! pointers and line numbers are ignored, since they change with nearly every
+//! modification of the code.
+use crate::options;
+use crate::valgrind::xml::Error;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// The comment at the start of every baseline file.
+const HEADER: &str = "\
+# Known errors reported by `cargo valgrind`, one fingerprint per line.
+# Regenerate this file by running with `--baseline <FILE> --write-baseline`.
+";
+
+/// A set of known errors.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Baseline {
+    /// The fingerprints of all known errors.
+    fingerprints: BTreeSet<String>,
+}
+impl Baseline {
+    /// Load the baseline from the given file.
+    ///
+    /// Empty lines and lines starting with `#` are ignored.
+    ///
+    /// # Errors
+    /// Any I/O error during reading the file is returned.
+    pub fn load(file: &Path) -> io::Result<Self> {
+        let contents = fs::read_to_string(file)?;
+        let fingerprints = contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(String::from)
+            .collect();
+        Ok(Self { fingerprints })
+    }
+
+    /// Create a baseline containing all the given errors.
+    pub fn from_errors<'a, I: IntoIterator<Item = &'a Error>>(errors: I) -> Self {
+        Self {
+            fingerprints: errors.into_iter().map(fingerprint).collect(),
+        }
+    }
+
+    /// Write the baseline to the given file.
+    ///
+    /// # Errors
+    /// Any I/O error during writing the file is returned.
+    pub fn write(&self, file: &Path) -> io::Result<()> {
+        let mut contents = String::from(HEADER);
+        for fingerprint in &self.fingerprints {
+            contents.push_str(fingerprint);
+            contents.push('\n');
+        }
+        fs::write(file, contents)
+    }
+
+    /// The number of known errors.
+    pub fn len(&self) -> usize {
+        self.fingerprints.len()
+    }
+
+    /// Check, whether the error is contained in the baseline.
+    pub fn contains(&self, error: &Error) -> bool {
+        self.fingerprints.contains(&fingerprint(error))
+    }
+
+    /// Query the fingerprints of the baseline, which don't match any of the
+    /// given errors, i.e. the errors, that were fixed.
+    pub fn fixed<'a, I: IntoIterator<Item = &'a Error>>(&self, errors: I) -> Vec<&str> {
+        let seen = Self::from_errors(errors);
+        self.fingerprints
+            .iter()
+            .filter(|fingerprint| !seen.fingerprints.contains(*fingerprint))
+            .map(String::as_str)
+            .collect()
+    }
+}
+
+/// The errors known from the baseline, which were found while running a
+/// program.
+///
+/// Only the first error of every fingerprint is kept, since this is all that is
+/// needed to write or check the baseline. Therefore the memory does not grow
+/// with the number of reported errors, but only with the number of distinct
+/// ones.
+#[derive(Debug, Default)]
+pub struct Known {
+    /// The number of all known errors found.
+    count: usize,
+    /// The first error found for every fingerprint.
+    errors: BTreeMap<String, Error>,
+}
+impl Known {
+    /// Add a known error.
+    pub fn insert(&mut self, error: Error) {
+        self.count += 1;
+        self.errors.entry(fingerprint(&error)).or_insert(error);
+    }
+
+    /// The number of known errors found, including repeated ones.
+    pub const fn count(&self) -> usize {
+        self.count
+    }
+
+    /// The distinct known errors ordered by their fingerprints.
+    pub fn into_errors(self) -> Vec<Error> {
+        self.errors.into_values().collect()
+    }
+}
+
+/// Split the errors into new errors and errors known from the baseline, that
+/// is selected via the options.
+///
+/// If the baseline is currently written, all errors are considered known. If no
+/// baseline is selected (or it cannot be read), all errors are new.
+pub fn split(errors: Vec<Error>) -> (Vec<Error>, Vec<Error>) {
+    let is_new = is_new();
+    errors.into_iter().partition(|error| is_new(error))
+}
+
+/// Create a predicate, whether an error is new, i.e. not known from the
+/// baseline selected via the options (see [`split()`]).
+///
+/// The baseline is loaded only once, so that the predicate can be applied to
+/// every error as soon as it is reported.
+pub fn is_new() -> impl Fn(&Error) -> bool + Send + 'static {
+    let file = options::baseline_file();
+    let writing = file.is_some() && options::write_baseline();
+    let baseline = file
+        .filter(|_| !writing)
+        .map(|file| Baseline::load(&file).unwrap_or_default());
+    move |error| {
+        !writing
+            && !baseline
+                .as_ref()
+                .is_some_and(|baseline| baseline.contains(error))
+    }
+}
+
+/// Compute the fingerprint of an error.
+///
+/// This is the kind of the error followed by `function@file` for every frame of
+/// the main stack trace. Unknown functions are replaced by the object file
+/// name or `?`.
+pub fn fingerprint(error: &Error) -> String {
+    let frames = error.stack_trace.first().map_or_else(Vec::new, |stack| {
+        stack
+            .frames
+            .iter()
+            .map(|frame| {
+                let function = frame.function.as_deref().unwrap_or_else(|| {
+                    frame
+                        .object
+                        .as_deref()
+                        .and_then(|object| object.rsplit('/').next())
+                        .unwrap_or("?")
+                });
+                frame
+                    .file
+                    .as_ref()
+                    .map_or_else(|| function.to_string(), |file| format!("{function}@{file}"))
+            })
+            .collect()
+    });
+    format!("{} {}", error.kind.id(), frames.join(";"))
+}
@@ -1,10 +1,12 @@
 //! A module providing the wrapping driver for a custom runner.
 
-use std::env;
-use std::ffi::OsString;
-use std::io;
-use std::path::Path;
-use std::process::{Command, ExitStatus};
+use crate::{archive, baseline, options, output, results, valgrind};
+use colored::Colorize as _;
+use serde::Deserialize;
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
+use std::process::{self, Command, Stdio};
+use std::{env, fs, io};
 
 /// The prefix line for the target host output.
 const HOST_PREFIX: &str = "host: ";
@@ -20,25 +22,110 @@ fn search_for_host(command: &mut Command) -> Option<String> {
         .map(|host_line| host_line.trim_start_matches(HOST_PREFIX).to_string())
 }
 
+/// The parts of the `cargo metadata` output used by `cargo valgrind`.
+#[derive(Debug, Deserialize)]
+struct Metadata {
+    /// The root directory of the workspace, that cargo operates on.
+    workspace_root: PathBuf,
+    /// The directory containing all the build artifacts.
+    target_directory: PathBuf,
+}
+
+/// Query the metadata of the workspace, that cargo operates on.
+///
+/// A `--manifest-path` given in the cargo arguments is respected.
+fn metadata(cargo: &OsStr, cargo_args: &[OsString]) -> Option<Metadata> {
+    let mut command = Command::new(cargo);
+    command.args(["metadata", "--no-deps", "--format-version", "1"]);
+    let mut args = cargo_args.iter().take_while(|arg| *arg != "--");
+    while let Some(arg) = args.next() {
+        let arg = arg.to_string_lossy();
+        if arg == "--manifest-path" {
+            command.arg("--manifest-path").args(args.next());
+        } else if arg.starts_with("--manifest-path=") {
+            command.arg(&*arg);
+        }
+    }
+
+    let output = command
+        .stderr(Stdio::null())
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    serde_json::from_slice(&output.stdout).ok()
+}
+
+/// Query the sysroot of the active toolchain via `rustc --print sysroot`.
+fn sysroot(rustc: &Path) -> Option<PathBuf> {
+    let output = Command::new(rustc)
+        .args(["--print", "sysroot"])
+        .stderr(Stdio::null())
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    let sysroot = String::from_utf8(output.stdout).ok()?;
+    Some(PathBuf::from(sysroot.trim_end()))
+}
+
 /// Act as a driver for `cargo run`/`cargo test`, but with special runner.
 ///
-/// This function returns `Ok(true)` if all subprograms were successfully
-/// executed, or `Ok(false)` if there was a non-successful subcommand.
+/// This function returns the exit code of cargo. If the cargo subcommand is
+/// `report`, the given valgrind XML files are rendered instead (see
+/// [`report()`]) and its exit code is returned. The pseudo-subcommand
+/// `flamegraph` runs the following cargo subcommand under callgrind and writes
+/// a flamegraph of every binary into the target directory.
 ///
 /// # Errors
 /// This function returns an I/O error, if a subprocess could not be spawned or
-/// executed.
-pub fn driver() -> io::Result<ExitStatus> {
+/// executed. Invalid options of `cargo valgrind` itself are reported as an I/O
+/// error of kind [`io::ErrorKind::InvalidInput`].
+pub fn driver() -> io::Result<i32> {
+    let (options, mut cargo_args) = options::split(env::args_os().skip(2))
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    // apply the options to the own environment, so that they are available
+    // to this process as well as inherited by cargo and therefore the runner.
+    for (name, value) in options {
+        env::set_var(name, value);
+    }
+
     let cargo = env::var_os("CARGO").expect("CARGO environment variable is not set");
     let rustc = Path::new(&cargo).with_file_name("rustc");
 
+    let metadata = metadata(&cargo, &cargo_args);
+    if let Some(metadata) = &metadata {
+        env::set_var(options::WORKSPACE, &metadata.workspace_root);
+    }
+    if let Some(sysroot) = sysroot(&rustc) {
+        env::set_var(options::SYSROOT, sysroot);
+    }
+    check_baseline()?;
+    check_results()?;
+    if let Some(code) = valgrind::ignored_error_exitcode() {
+        output::display_ignored_error_exitcode(&code);
+    }
+    if cargo_args.first().is_some_and(|arg| arg == "report") {
+        return report(&cargo_args[1..]);
+    }
+    if cargo_args.first().is_some_and(|arg| arg == "flamegraph") {
+        // the flamegraph is written into the target directory
+        if metadata.is_none() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "`cargo valgrind flamegraph` could not determine the target directory",
+            ));
+        }
+        cargo_args.remove(0);
+        env::set_var(options::TOOL, options::Tool::Callgrind.name());
+        env::set_var(options::FLAMEGRAPH, "1");
+    }
+
     // Search for the host currently running to be able to override the runner.
     // The host field is extracted from `cargo version -v` if possible, since
     // this relies entirely on the used `cargo` binary. Older versions of cargo
     // don't provide the host in that output, though, so there is a fallback to
     // `rustc -vV` in that case.
     let host = search_for_host(Command::new(&cargo).arg("version").arg("-v"))
-        .or_else(|| search_for_host(Command::new(rustc).arg("rustc").arg("-vV")))
+        .or_else(|| search_for_host(Command::new(&rustc).arg("rustc").arg("-vV")))
         .ok_or_else(|| io::Error::other("could not determine host"))?;
 
     /* convert to runner env variable */
@@ -50,10 +137,197 @@ pub fn driver() -> io::Result<ExitStatus> {
         .next()
         .unwrap_or_else(|| OsString::from("cargo-valgrind"));
 
-    Command::new(cargo)
-        .args(env::args_os().skip(2))
+    // Reports spanning all analyzed binaries can only be created after cargo
+    // is done. Therefore each runner invocation stores its report inside of a
+    // temporary directory, which is evaluated afterwards.
+    let needs_reports = options::message_format() == options::MessageFormat::Json
+        || options::sarif_file().is_some()
+        || options::junit_file().is_some()
+        || options::folded_file().is_some()
+        || options::baseline_file().is_some()
+        || options::write_results();
+    let report_directory = if needs_reports {
+        let directory = env::temp_dir().join(format!("cargo-valgrind-{}", process::id()));
+        fs::create_dir_all(&directory)?;
+        Some(directory)
+    } else {
+        None
+    };
+
+    // The raw output of every runner invocation is archived inside of the target
+    // directory. The index of all runs of this invocation is started afresh.
+    if let Some(metadata) = &metadata {
+        let archive = metadata.target_directory.join("valgrind");
+        match archive::Archive::start_index(&archive) {
+            Ok(()) => env::set_var(options::ARCHIVE_DIRECTORY, archive),
+            Err(e) => eprintln!(
+                "{}: could not archive the runs: {e}",
+                "warning".yellow().bold()
+            ),
+        }
+    }
+
+    let mut command = Command::new(&cargo);
+    command
+        .args(&cargo_args)
         .envs(env::vars_os())
-        .env(runner, cargo_valgrind)
-        .spawn()?
-        .wait()
+        .env(runner, cargo_valgrind);
+    if let Some(directory) = &report_directory {
+        command.env(options::REPORT_DIRECTORY, directory);
+    }
+    let exit_status = command.spawn()?.wait()?;
+
+    if let Some(directory) = report_directory {
+        let reports = output::Report::load_all(&directory);
+        fs::remove_dir_all(&directory).ok();
+        write_reports(&reports?)?;
+    }
+    Ok(exit_status.code().unwrap_or(200))
+}
+
+/// Render saved valgrind XML files (`cargo valgrind report <file.xml>...`).
+///
+/// Each file is parsed as if it was produced by running a binary via `cargo
+/// valgrind` and is displayed in all the requested output formats, but nothing
+/// is executed. The exit code is `127` if any error was found and `0` otherwise.
+///
+/// # Errors
+/// Missing, unreadable or malformed files are reported as an I/O error of kind
+/// [`io::ErrorKind::InvalidInput`]. Errors during writing the reports are
+/// returned as well.
+fn report(files: &[OsString]) -> io::Result<i32> {
+    if files.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "`cargo valgrind report` requires at least one valgrind XML file",
+        ));
+    }
+
+    let mut reports = Vec::new();
+    for file in files.iter().map(Path::new) {
+        let invalid_input = |e: &dyn std::fmt::Display| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("could not read `{}`: {e}", file.display()),
+            )
+        };
+        let xml = fs::read(file).map_err(|e| invalid_input(&e))?;
+        let xml = valgrind::parse(&xml).map_err(|e| invalid_input(&e))?;
+        if let Some(version) = xml.newer_protocol_version() {
+            output::display_newer_protocol_version(version);
+        }
+
+        let mut report = output::Report::new(xml.args.map(|args| args.program.exe));
+        let (errors, known_errors) = baseline::split(xml.errors.unwrap_or_default());
+        output::display_known_errors(known_errors.len());
+        if !errors.is_empty() {
+            output::display_errors(&errors);
+            report.exit_code = 127;
+        }
+        report.errors = errors;
+        report.known_errors = known_errors;
+        output::display_formats(&report);
+        reports.push(report);
+    }
+    write_reports(&reports)?;
+
+    Ok(reports
+        .iter()
+        .map(|report| report.exit_code)
+        .max()
+        .unwrap_or(0))
+}
+
+/// Check, that the baseline options are consistent and the baseline is
+/// readable, before anything is executed.
+///
+/// # Errors
+/// Problems with the baseline are reported as an I/O error of kind
+/// [`io::ErrorKind::InvalidInput`].
+fn check_baseline() -> io::Result<()> {
+    let invalid_input = |message| io::Error::new(io::ErrorKind::InvalidInput, message);
+    match options::baseline_file() {
+        None if options::write_baseline() => Err(invalid_input(String::from(
+            "`--write-baseline` requires a baseline file via `--baseline <FILE>`",
+        ))),
+        Some(file) if !options::write_baseline() => {
+            baseline::Baseline::load(&file).map(|_| ()).map_err(|e| {
+                invalid_input(format!(
+                    "could not read baseline `{}`: {e} (use `--write-baseline` to create it)",
+                    file.display()
+                ))
+            })
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Check, that the results options are consistent and the recorded results are
+/// readable, before anything is executed.
+///
+/// # Errors
+/// Problems with the results file are reported as an I/O error of kind
+/// [`io::ErrorKind::InvalidInput`].
+fn check_results() -> io::Result<()> {
+    let invalid_input = |message| io::Error::new(io::ErrorKind::InvalidInput, message);
+    match options::results_file() {
+        None if options::write_results() => Err(invalid_input(String::from(
+            "`--write-results` requires a results file via `--results <FILE>`",
+        ))),
+        Some(file) if !options::write_results() => {
+            results::Results::load(&file).map(|_| ()).map_err(|e| {
+                invalid_input(format!(
+                    "could not read results `{}`: {e} (use `--write-results` to create it)",
+                    file.display()
+                ))
+            })
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Write all requested reports, that span all analyzed binaries.
+///
+/// # Errors
+/// Any I/O error during writing the reports is returned.
+fn write_reports(reports: &[output::Report]) -> io::Result<()> {
+    let workspace = options::workspace();
+    if options::message_format() == options::MessageFormat::Json {
+        output::json::display_reports(reports)?;
+    }
+    if let Some(file) = options::sarif_file() {
+        output::sarif::write(&file, reports, workspace.as_deref())?;
+    }
+    if let Some(file) = options::junit_file() {
+        output::junit::write(&file, reports)?;
+    }
+    if let Some(file) = options::folded_file() {
+        output::folded::write(&file, reports)?;
+    }
+    if let Some(file) = options::baseline_file() {
+        let errors = reports
+            .iter()
+            .flat_map(|report| report.errors.iter().chain(&report.known_errors));
+        if options::write_baseline() {
+            let baseline = baseline::Baseline::from_errors(errors);
+            baseline.write(&file)?;
+            output::display_baseline_written(&file, baseline.len());
+        } else {
+            let baseline = baseline::Baseline::load(&file)?;
+            output::display_fixed_errors(&baseline.fixed(errors));
+        }
+    }
+    if let Some(file) = options::results_file().filter(|_| options::write_results()) {
+        // keep the results of binaries, which were not run this time
+        let mut results = results::Results::load(&file).unwrap_or_default();
+        for report in reports {
+            if let Some(instructions) = report.instructions {
+                let binary = report.binary.as_deref().unwrap_or_default();
+                results.insert(results::name(binary), instructions);
+            }
+        }
+        results.write(&file)?;
+        output::display_results_written(&file, results.len());
+    }
+    Ok(())
 }
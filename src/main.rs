@@ -17,14 +17,21 @@
     clippy::used_underscore_binding
 )]
 
+mod archive;
+mod baseline;
 mod driver;
+mod options;
 mod output;
 mod panic;
+mod profile;
+mod results;
 mod valgrind;
 
 use colored::Colorize as _;
-use std::env;
+use std::ffi::OsStr;
 use std::process;
+use std::sync::{Arc, Mutex};
+use std::{env, io};
 
 fn main() {
     panic::replace_hook();
@@ -43,7 +50,14 @@ fn main() {
             \"target\", what valgrind should do. For example: `cargo valgrind \
             run` will do the same thing as `cargo run` (i.e. compile and run \
             your binary), but the execution will be done using valgrind. \
-            Similarly to execute the tests, simply use `cargo valgrind test`.",
+            Similarly to execute the tests, simply use `cargo valgrind test`. \
+            Previously saved valgrind XML output can be rendered with `cargo \
+            valgrind report <file.xml>...` without running anything. \
+            A flamegraph of the executed instructions of every binary is \
+            written into the target directory with `cargo valgrind flamegraph \
+            run` (or `test` or `bench`). \
+            Options of `cargo valgrind` itself have to be given before the \
+            cargo subcommand, e.g. `cargo valgrind --message-format=json test`.",
             version = env!("CARGO_PKG_VERSION"),
             authors = env!("CARGO_PKG_AUTHORS").replace(':', ", "),
         );
@@ -54,51 +68,167 @@ fn main() {
         )
         .join("\n");
         println!("{text}");
+
+        // intentionally not wrapped using `textwrap` to keep the alignment
+        print!("\nOptions:\n{}", options::help());
     } else if is_cargo_subcommand() {
-        let exit_status = driver::driver().expect("Could not execute subcommand");
-        process::exit(exit_status.code().unwrap_or(200));
+        let exit_code = match driver::driver() {
+            Ok(exit_code) => exit_code,
+            Err(e) if e.kind() == io::ErrorKind::InvalidInput => {
+                eprintln!("{}: {e}", "error".red().bold());
+                1
+            }
+            result => result.expect("Could not execute subcommand"),
+        };
+        process::exit(exit_code);
+    } else {
+        process::exit(run());
+    }
+}
+
+/// Run the program under test inside of valgrind and report the results.
+///
+/// This is done, if this binary acts as the cargo runner. The exit code to use
+/// for the process is returned.
+fn run() -> i32 {
+    // we are running as the cargo runner, therefore everything except the
+    // first argument is the command to execute.
+    let command = env::args_os().skip(1);
+    let binary = env::args_os()
+        .nth(1)
+        .map(|binary| binary.to_string_lossy().into_owned());
+    let mut report = output::Report::new(binary);
+    let archive = options::archive_directory().and_then(|directory| {
+        let binary = env::args_os().nth(1).unwrap_or_default();
+        archive::Archive::create(&directory, &binary)
+            .map_err(|e| {
+                eprintln!(
+                    "{}: could not archive the run: {e}",
+                    "warning".yellow().bold()
+                );
+            })
+            .ok()
+    });
+
+    let tool = options::tool();
+    let exit_code = if tool.is_profiler() {
+        profile::run(tool, command, archive.as_ref(), &mut report)
     } else {
-        // we are running as the cargo runner, therefore everything except the
-        // first argument is the command to execute.
-        let command = env::args_os().skip(1);
+        check(command, archive.as_ref(), &mut report)
+    };
 
-        let exit_code = match valgrind::execute(command) {
-            Ok(valgrind::xml::Output {
-                errors: Some(errors),
-                ..
-            }) => {
-                output::display_errors(&errors);
-                127
+    report.exit_code = exit_code;
+    output::display_formats(&report);
+    if let Some(archive) = &archive {
+        archive.store_report(&report).ok();
+    }
+    if let Some(directory) = options::report_directory() {
+        if let Err(e) = report.save(&directory) {
+            eprintln!("{}: could not store report: {e}", "error".red().bold());
+        }
+    }
+    exit_code
+}
+
+/// Check the command for errors with the selected error detector and report
+/// them.
+///
+/// The new errors are displayed as soon as valgrind reports them, while the
+/// summary is displayed after the program exited. The exit code to use for the
+/// process is returned.
+fn check<S, I>(command: I, archive: Option<&archive::Archive>, report: &mut output::Report) -> i32
+where
+    S: AsRef<OsStr>,
+    I: IntoIterator<Item = S>,
+{
+    // only the new errors are kept completely, the known ones are condensed
+    // to what is needed to write and check the baseline.
+    let is_new = baseline::is_new();
+    let found = Arc::new(Mutex::new((Vec::new(), baseline::Known::default())));
+    let result = valgrind::execute(command, archive, {
+        let found = Arc::clone(&found);
+        move |error| {
+            let mut found = found.lock().expect("Error-handler panicked");
+            if is_new(&error) {
+                output::display_error(&error);
+                found.0.push(error);
+            } else {
+                found.1.insert(error);
+            }
+        }
+    });
+    let (errors, known) = std::mem::take(&mut *found.lock().expect("Error-handler panicked"));
+    let known_count = known.count();
+    let known_errors = known.into_errors();
+    let xml = match &result {
+        Ok(xml) => Some(xml),
+        Err(valgrind::Error::ProcessSignal(_, xml) | valgrind::Error::ProcessExit(_, xml)) => {
+            Some(&**xml)
+        }
+        Err(_) => None,
+    };
+    if let Some(xml) = xml {
+        if let Some(version) = xml.newer_protocol_version() {
+            output::display_newer_protocol_version(version);
+        }
+        output::display_child_processes(&xml.children);
+    }
+    match result {
+        Ok(_) => {
+            output::display_known_errors(known_count);
+            if !errors.is_empty() {
+                output::display_summary(&errors);
             }
-            Ok(_) => 0,
-            Err(valgrind::Error::ProcessSignal(
-                signal_nr,
-                valgrind::xml::Output {
-                    errors: Some(errors),
-                    ..
-                },
-            )) => {
-                output::display_errors(&errors);
+            let code = if errors.is_empty() { 0 } else { 127 };
+            if !errors.is_empty() || known_count > 0 {
+                // valgrind replaces the exit code of the program, as soon as
+                // it found any error (even a known one).
                 eprintln!(
-                    "{}: the program was terminated by signal {signal_nr}",
+                    "{}: the exit code of the program is unknown, since valgrind replaces it if errors were found",
                     "info".cyan().bold()
                 );
-                128 + signal_nr
             }
-            Err(valgrind::Error::ProcessSignal(signal_nr, _)) => {
+            report.errors = errors;
+            report.known_errors = known_errors;
+            code
+        }
+        Err(valgrind::Error::ProcessSignal(signal_nr, _)) => {
+            output::display_known_errors(known_count);
+            if errors.is_empty() {
                 eprintln!("{}: no memory error was detected, but the program was terminated by signal {signal_nr}", "info".cyan().bold());
-                128 + signal_nr
-            }
-            Err(e @ valgrind::Error::MalformedOutput(..)) => std::panic::panic_any(e), // the panic handler catches this and reports it appropriately
-            Err(valgrind::Error::StackOverflow(output)) => {
-                output::display_stack_overflow(&output);
-                134 // default exit code for stack overflows
+            } else {
+                output::display_summary(&errors);
+                eprintln!(
+                    "{}: the program was terminated by signal {signal_nr}",
+                    "info".cyan().bold()
+                );
             }
-            Err(e) => {
-                eprintln!("{}: {}", "error".red().bold(), e);
-                1
+            report.errors = errors;
+            report.known_errors = known_errors;
+            report.signal = Some(signal_nr);
+            128 + signal_nr
+        }
+        Err(valgrind::Error::ProcessExit(code, _)) => {
+            output::display_known_errors(known_count);
+            if !errors.is_empty() {
+                output::display_summary(&errors);
             }
-        };
-        process::exit(exit_code);
+            eprintln!(
+                "{}: the program exited with code {code}",
+                "info".cyan().bold()
+            );
+            report.errors = errors;
+            report.known_errors = known_errors;
+            code
+        }
+        Err(e @ valgrind::Error::MalformedOutput(..)) => std::panic::panic_any(e), // the panic handler catches this and reports it appropriately
+        Err(valgrind::Error::StackOverflow(output)) => {
+            output::display_stack_overflow(&output);
+            134 // default exit code for stack overflows
+        }
+        Err(e) => {
+            eprintln!("{}: {}", "error".red().bold(), e);
+            1
+        }
     }
 }
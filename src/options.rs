@@ -0,0 +1,511 @@
+//! Options of `cargo valgrind` itself.
+//!
+//! Those options have to be given before the cargo subcommand, e.g. `cargo
+//! valgrind --message-format=json test`, so that they are not mixed up with the
+//! options of cargo. The actual analysis is performed by a separate invocation
+//! of this binary (acting as the cargo runner), therefore the options are
+//! forwarded to it via environment variables. Those variables might be set by
+//! the user directly as well.
+
+use bytesize::ByteSize;
+use std::env;
+use std::ffi::OsString;
+use std::fmt::Write as _;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// The environment variable containing the [`MessageFormat`].
+const FORMAT: &str = "CARGO_VALGRIND_FORMAT";
+/// The environment variable containing the valgrind [`Tool`] to use.
+pub const TOOL: &str = "CARGO_VALGRIND_TOOL";
+/// The environment variable containing the heap budget of massif.
+const MAX_HEAP: &str = "CARGO_VALGRIND_MAX_HEAP";
+/// The environment variable enabling the annotated source listing of
+/// cachegrind.
+const ANNOTATE: &str = "CARGO_VALGRIND_ANNOTATE";
+/// The environment variable containing the path of the callgrind results file.
+const RESULTS: &str = "CARGO_VALGRIND_RESULTS";
+/// The environment variable enabling writing the callgrind results file.
+const WRITE_RESULTS: &str = "CARGO_VALGRIND_WRITE_RESULTS";
+/// The environment variable containing the allowed instruction count
+/// regression in percent.
+const MAX_REGRESSION: &str = "CARGO_VALGRIND_MAX_REGRESSION";
+/// The environment variable enabling the tracking of file descriptors.
+const CHECK_FDS: &str = "CARGO_VALGRIND_CHECK_FDS";
+/// The environment variable enabling the analysis of child processes.
+const TRACE_CHILDREN: &str = "CARGO_VALGRIND_TRACE_CHILDREN";
+/// The environment variable containing the path of the SARIF report file.
+const SARIF: &str = "CARGO_VALGRIND_SARIF";
+/// The environment variable containing the path of the `JUnit` report file.
+const JUNIT: &str = "CARGO_VALGRIND_JUNIT";
+/// The environment variable containing the path of the folded leak stacks file.
+const FOLDED: &str = "CARGO_VALGRIND_FOLDED";
+/// The environment variable enabling the GitHub Actions annotations.
+const GITHUB_ANNOTATIONS: &str = "CARGO_VALGRIND_GITHUB_ANNOTATIONS";
+/// The environment variable containing the path of the baseline file.
+const BASELINE: &str = "CARGO_VALGRIND_BASELINE";
+/// The environment variable enabling writing the baseline file.
+const WRITE_BASELINE: &str = "CARGO_VALGRIND_WRITE_BASELINE";
+/// The environment variable enabling the elision of generic arguments.
+const ELIDE_GENERICS: &str = "CARGO_VALGRIND_ELIDE_GENERICS";
+/// The environment variable disabling the collapsing of noise frames.
+const FULL_STACKS: &str = "CARGO_VALGRIND_FULL_STACKS";
+/// The environment variable containing the root directory of the workspace.
+///
+/// This is set by the driver and is not meant to be set by the user.
+pub const WORKSPACE: &str = "CARGO_VALGRIND_WORKSPACE";
+/// The environment variable containing the sysroot of the active toolchain.
+///
+/// This is set by the driver and is not meant to be set by the user.
+pub const SYSROOT: &str = "CARGO_VALGRIND_SYSROOT";
+/// The environment variable enabling the flamegraph of callgrind runs.
+///
+/// This is set by the driver for `cargo valgrind flamegraph` and is not meant
+/// to be set by the user.
+pub const FLAMEGRAPH: &str = "CARGO_VALGRIND_FLAMEGRAPH";
+/// The environment variable containing the directory, in which the raw output
+/// of every runner invocation is archived.
+///
+/// This is set by the driver to `valgrind/` inside of the target directory.
+pub const ARCHIVE_DIRECTORY: &str = "CARGO_VALGRIND_ARCHIVE_DIR";
+/// The environment variable containing the directory, in which each runner
+/// invocation stores its report.
+///
+/// This is set by the driver, if any report spanning all analyzed binaries is
+/// requested and is not meant to be set by the user.
+pub const REPORT_DIRECTORY: &str = "CARGO_VALGRIND_REPORT_DIR";
+
+/// A command line option and the environment variable it is forwarded as.
+struct Flag {
+    /// The long name of the option including the leading dashes.
+    name: &'static str,
+    /// The placeholder of the option value shown in the help text.
+    ///
+    /// Options without a value are switches, which are forwarded as `1`.
+    value: Option<&'static str>,
+    /// The environment variable, which receives the option value.
+    env: &'static str,
+    /// A short description of the option shown in the help text.
+    help: &'static str,
+    /// Check, whether the given value is acceptable for this option.
+    validate: fn(&str) -> Result<(), String>,
+}
+
+/// All the options known to `cargo valgrind`.
+const FLAGS: &[Flag] = &[
+    Flag {
+        name: "--message-format",
+        value: Some("<FMT>"),
+        env: FORMAT,
+        help: "`human` (default) or `json` (adds JSON to stdout)",
+        validate: |value| value.parse::<MessageFormat>().map(drop),
+    },
+    Flag {
+        name: "--tool",
+        value: Some("<TOOL>"),
+        env: TOOL,
+        help:
+            "`memcheck` (default), `helgrind`, `drd`, `massif`, `dhat`, `callgrind` or `cachegrind`",
+        validate: |value| value.parse::<Tool>().map(drop),
+    },
+    Flag {
+        name: "--max-heap",
+        value: Some("<SIZE>"),
+        env: MAX_HEAP,
+        help: "fail if the peak heap usage exceeds SIZE (massif only)",
+        validate: |value| value.parse::<ByteSize>().map(drop),
+    },
+    Flag {
+        name: "--annotate",
+        value: None,
+        env: ANNOTATE,
+        help: "show the source of the hottest lines (cachegrind only)",
+        validate: |_| Ok(()),
+    },
+    Flag {
+        name: "--results",
+        value: Some("<FILE>"),
+        env: RESULTS,
+        help: "compare instruction counts to the FILE (callgrind only)",
+        validate: |_| Ok(()),
+    },
+    Flag {
+        name: "--write-results",
+        value: None,
+        env: WRITE_RESULTS,
+        help: "record all current instruction counts in the results FILE",
+        validate: |_| Ok(()),
+    },
+    Flag {
+        name: "--max-regression",
+        value: Some("<PCT>"),
+        env: MAX_REGRESSION,
+        help: "allowed instruction count increase (default 1%)",
+        validate: |value| parse_percentage(value).map(drop),
+    },
+    Flag {
+        name: "--check-fds",
+        value: None,
+        env: CHECK_FDS,
+        help: "report file descriptors, that were not closed",
+        validate: |_| Ok(()),
+    },
+    Flag {
+        name: "--trace-children",
+        value: None,
+        env: TRACE_CHILDREN,
+        help: "also check the child processes of the program",
+        validate: |_| Ok(()),
+    },
+    Flag {
+        name: "--sarif",
+        value: Some("<FILE>"),
+        env: SARIF,
+        help: "write a SARIF report of all binaries to FILE",
+        validate: |_| Ok(()),
+    },
+    Flag {
+        name: "--junit",
+        value: Some("<FILE>"),
+        env: JUNIT,
+        help: "write a JUnit XML report of all binaries to FILE",
+        validate: |_| Ok(()),
+    },
+    Flag {
+        name: "--folded",
+        value: Some("<FILE>"),
+        env: FOLDED,
+        help: "write the leaked bytes as folded stacks to FILE",
+        validate: |_| Ok(()),
+    },
+    Flag {
+        name: "--github-annotations",
+        value: None,
+        env: GITHUB_ANNOTATIONS,
+        help: "print GitHub Actions annotations (default on CI)",
+        validate: |_| Ok(()),
+    },
+    Flag {
+        name: "--baseline",
+        value: Some("<FILE>"),
+        env: BASELINE,
+        help: "only fail on errors not listed in the baseline FILE",
+        validate: |_| Ok(()),
+    },
+    Flag {
+        name: "--write-baseline",
+        value: None,
+        env: WRITE_BASELINE,
+        help: "record all current errors in the baseline FILE",
+        validate: |_| Ok(()),
+    },
+    Flag {
+        name: "--elide-generics",
+        value: None,
+        env: ELIDE_GENERICS,
+        help: "shorten generic arguments in stack traces to `<…>`",
+        validate: |_| Ok(()),
+    },
+    Flag {
+        name: "--full-stacks",
+        value: None,
+        env: FULL_STACKS,
+        help: "show allocator, runtime and system frames in stack traces",
+        validate: |_| Ok(()),
+    },
+];
+
+/// Environment variables (name and value) forwarding the options to the runner.
+pub type Environment = Vec<(&'static str, OsString)>;
+
+/// Create the help text listing all the options.
+///
+/// Each option is listed on its own line(s) together with its description and
+/// the environment variable, that can be used instead.
+pub fn help() -> String {
+    FLAGS.iter().fold(String::new(), |mut help, flag| {
+        let option = flag.value.map_or_else(
+            || flag.name.to_string(),
+            |value| format!("{} {value}", flag.name),
+        );
+        writeln!(help, "    {option:<26}{}", flag.help).expect("writing to a string never fails");
+        writeln!(help, "    {:<26}[env: {}]", "", flag.env)
+            .expect("writing to a string never fails");
+        help
+    })
+}
+
+/// Split the command line into the options of `cargo valgrind` and the
+/// arguments to pass to cargo.
+///
+/// The options are returned as pairs of environment variable names and their
+/// values. Options are accepted in the form `--name=value` or `--name value`.
+/// Parsing stops at the first argument, that is not a known option: it and all
+/// of the following arguments are returned as the cargo arguments.
+///
+/// # Errors
+/// An error message is returned, if an option is missing its value or if the
+/// value is invalid.
+pub fn split<I>(args: I) -> Result<(Environment, Vec<OsString>), String>
+where
+    I: IntoIterator<Item = OsString>,
+{
+    let mut args = args.into_iter().peekable();
+    let mut options = Vec::new();
+    while let Some(arg) = args.peek() {
+        let arg = arg.to_string_lossy();
+        let (name, value) = match arg.split_once('=') {
+            Some((name, value)) => (name, Some(value.to_string())),
+            None => (&*arg, None),
+        };
+        let Some(flag) = FLAGS.iter().find(|flag| flag.name == name) else {
+            break;
+        };
+        args.next();
+        let value = match (flag.value, value) {
+            (None, Some(_)) => return Err(format!("`{}` does not take a value", flag.name)),
+            (None, None) => String::from("1"),
+            (Some(_), Some(value)) => value,
+            (Some(_), None) => args
+                .next()
+                .map(|value| value.to_string_lossy().into_owned())
+                .ok_or_else(|| format!("missing value for `{}`", flag.name))?,
+        };
+        (flag.validate)(&value).map_err(|e| format!("invalid value for `{}`: {e}", flag.name))?;
+        options.push((flag.env, value.into()));
+    }
+    Ok((options, args.collect()))
+}
+
+/// The format of the messages printed in addition to the human-readable report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageFormat {
+    /// Only print the human-readable report to stderr.
+    Human,
+    /// Additionally print a JSON document per analyzed binary to stdout.
+    Json,
+}
+impl FromStr for MessageFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(Self::Human),
+            "json" => Ok(Self::Json),
+            _ => Err(format!(
+                "unknown message format `{s}` (expected `human` or `json`)"
+            )),
+        }
+    }
+}
+
+/// Query the selected [`MessageFormat`].
+///
+/// Unset or invalid values fall back to [`MessageFormat::Human`].
+pub fn message_format() -> MessageFormat {
+    env::var(FORMAT)
+        .ok()
+        .and_then(|format| format.parse().ok())
+        .unwrap_or(MessageFormat::Human)
+}
+
+/// The valgrind tool used to analyze the program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tool {
+    /// The memory error detector (the default).
+    Memcheck,
+    /// The thread error detector `helgrind`.
+    Helgrind,
+    /// The thread error detector `drd`.
+    Drd,
+    /// The heap profiler `massif`.
+    Massif,
+    /// The allocation profiler `dhat`.
+    Dhat,
+    /// The call-graph profiler `callgrind`.
+    Callgrind,
+    /// The cache profiler `cachegrind`.
+    Cachegrind,
+}
+impl Tool {
+    /// The name of the tool as passed to `valgrind --tool=<name>`.
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Memcheck => "memcheck",
+            Self::Helgrind => "helgrind",
+            Self::Drd => "drd",
+            Self::Massif => "massif",
+            Self::Dhat => "dhat",
+            Self::Callgrind => "callgrind",
+            Self::Cachegrind => "cachegrind",
+        }
+    }
+
+    /// Query, whether the tool is a profiler, i.e. writes its results into an
+    /// output file instead of reporting errors via XML.
+    pub const fn is_profiler(self) -> bool {
+        match self {
+            Self::Memcheck | Self::Helgrind | Self::Drd => false,
+            Self::Massif | Self::Dhat | Self::Callgrind | Self::Cachegrind => true,
+        }
+    }
+}
+impl FromStr for Tool {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "memcheck" => Ok(Self::Memcheck),
+            "helgrind" => Ok(Self::Helgrind),
+            "drd" => Ok(Self::Drd),
+            "massif" => Ok(Self::Massif),
+            "dhat" => Ok(Self::Dhat),
+            "callgrind" => Ok(Self::Callgrind),
+            "cachegrind" => Ok(Self::Cachegrind),
+            _ => Err(format!(
+                "unknown tool `{s}` (expected `memcheck`, `helgrind`, `drd`, `massif`, `dhat`, `callgrind` or `cachegrind`)"
+            )),
+        }
+    }
+}
+
+/// Query the selected valgrind [`Tool`].
+///
+/// Unset or invalid values fall back to [`Tool::Memcheck`].
+pub fn tool() -> Tool {
+    env::var(TOOL)
+        .ok()
+        .and_then(|tool| tool.parse().ok())
+        .unwrap_or(Tool::Memcheck)
+}
+
+/// Query the heap budget, which must not be exceeded by the peak heap usage.
+pub fn max_heap() -> Option<ByteSize> {
+    env::var(MAX_HEAP).ok().and_then(|size| size.parse().ok())
+}
+
+/// Query, whether the source of the hottest lines should be annotated with
+/// their cache misses.
+pub fn annotate() -> bool {
+    is_enabled(ANNOTATE)
+}
+
+/// Query the path of the callgrind results file, if any.
+pub fn results_file() -> Option<PathBuf> {
+    env::var_os(RESULTS).map(PathBuf::from)
+}
+
+/// Query, whether the results file should be (re-)written with the current
+/// instruction counts.
+pub fn write_results() -> bool {
+    is_enabled(WRITE_RESULTS)
+}
+
+/// Query the allowed increase of the instruction count in percent.
+///
+/// Unset or invalid values fall back to 1%.
+pub fn max_regression() -> f64 {
+    env::var(MAX_REGRESSION)
+        .ok()
+        .and_then(|percentage| parse_percentage(&percentage).ok())
+        .unwrap_or(1.0)
+}
+
+/// Parse a non-negative percentage, optionally followed by `%`.
+fn parse_percentage(value: &str) -> Result<f64, String> {
+    value
+        .strip_suffix('%')
+        .unwrap_or(value)
+        .parse()
+        .ok()
+        .filter(|percentage: &f64| *percentage >= 0.0)
+        .ok_or_else(|| format!("`{value}` is not a non-negative percentage"))
+}
+
+/// Query, whether file descriptors should be tracked, i.e. whether leaked
+/// file descriptors and invalid closes should be reported.
+pub fn check_fds() -> bool {
+    is_enabled(CHECK_FDS)
+}
+
+/// Query, whether the child processes spawned by the program under test should
+/// be analyzed as well.
+pub fn trace_children() -> bool {
+    is_enabled(TRACE_CHILDREN)
+}
+
+/// Query the path of the SARIF report to write, if requested.
+pub fn sarif_file() -> Option<PathBuf> {
+    env::var_os(SARIF).map(PathBuf::from)
+}
+
+/// Query the path of the `JUnit` report to write, if requested.
+pub fn junit_file() -> Option<PathBuf> {
+    env::var_os(JUNIT).map(PathBuf::from)
+}
+
+/// Query the path of the folded stacks of the leaks to write, if requested.
+pub fn folded_file() -> Option<PathBuf> {
+    env::var_os(FOLDED).map(PathBuf::from)
+}
+
+/// Query, whether annotations for GitHub Actions should be printed.
+///
+/// Those are enabled by the option or automatically when running in GitHub
+/// Actions (i.e. if `GITHUB_ACTIONS` is `true`).
+pub fn github_annotations() -> bool {
+    is_enabled(GITHUB_ANNOTATIONS) || env::var_os("GITHUB_ACTIONS").is_some_and(|ci| ci == "true")
+}
+
+/// Query the path of the baseline file, if any.
+pub fn baseline_file() -> Option<PathBuf> {
+    env::var_os(BASELINE).map(PathBuf::from)
+}
+
+/// Query, whether the baseline file should be (re-)written with all errors.
+pub fn write_baseline() -> bool {
+    is_enabled(WRITE_BASELINE)
+}
+
+/// Query, whether generic arguments should be elided in stack traces.
+pub fn elide_generics() -> bool {
+    is_enabled(ELIDE_GENERICS)
+}
+
+/// Query, whether stack traces should be shown completely, i.e. without
+/// collapsing the allocator, runtime and system frames.
+pub fn full_stacks() -> bool {
+    is_enabled(FULL_STACKS)
+}
+
+/// Query the root directory of the workspace, if known.
+pub fn workspace() -> Option<PathBuf> {
+    env::var_os(WORKSPACE).map(PathBuf::from)
+}
+
+/// Query the sysroot of the active toolchain, if known.
+pub fn sysroot() -> Option<PathBuf> {
+    env::var_os(SYSROOT).map(PathBuf::from)
+}
+
+/// Query, whether a flamegraph of callgrind runs should be written.
+pub fn flamegraph() -> bool {
+    is_enabled(FLAMEGRAPH)
+}
+
+/// Query the directory, in which the raw output of every run is archived.
+pub fn archive_directory() -> Option<PathBuf> {
+    env::var_os(ARCHIVE_DIRECTORY).map(PathBuf::from)
+}
+
+/// Query the directory, in which the report of each binary should be stored.
+pub fn report_directory() -> Option<PathBuf> {
+    env::var_os(REPORT_DIRECTORY).map(PathBuf::from)
+}
+
+/// Check, whether the switch stored in the given environment variable is set.
+///
+/// Any value except for an empty one, `0` and `false` enables the switch.
+fn is_enabled(name: &str) -> bool {
+    env::var_os(name).is_some_and(|value| !value.is_empty() && value != "0" && value != "false")
+}
@@ -0,0 +1,225 @@
+//! Summary of the cache profile recorded by cachegrind.
+use super::frame::Origin;
+use super::snippet::Snippet;
+use crate::options;
+use crate::valgrind::cachegrind::{Cachegrind, Misses};
+use crate::valgrind::xml::Frame;
+use colored::Colorize as _;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// The number of functions and lines shown in the rankings.
+const TOP_ENTRIES: usize = 10;
+
+/// A function or source line together with its cache misses.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    /// The location of the function or line.
+    pub frame: Frame,
+    /// The executed instructions and cache misses of the location.
+    pub misses: Misses,
+}
+
+/// Print the total cache misses and the functions and workspace lines with the
+/// most cache misses.
+///
+/// If `annotate` is set, the source code around the hottest workspace lines is
+/// shown with the misses of every line.
+pub fn display(cachegrind: &Cachegrind, annotate: bool) {
+    let total = cachegrind.misses(&cachegrind.totals);
+    if cachegrind.has_cache_events() {
+        eprintln!(
+            "{:>12} {} instructions, {} I1 misses, {} D1 misses, {} LL misses",
+            "Summary".cyan().bold(),
+            total.instructions,
+            total.i1,
+            total.d1,
+            total.ll,
+        );
+    } else {
+        eprintln!(
+            "{:>12} {} instructions",
+            "Summary".cyan().bold(),
+            total.instructions
+        );
+        eprintln!(
+            "{:>12} the cache simulation was disabled (`--cache-sim=no`), so no misses were recorded",
+            "Info".cyan().bold()
+        );
+    }
+
+    let workspace = options::workspace();
+    display_ranking(
+        "top functions by cache misses",
+        &functions(cachegrind),
+        super::format_frame,
+    );
+    let lines = workspace_lines(cachegrind, workspace.as_deref());
+    display_ranking(
+        "top lines of the workspace by cache misses",
+        &lines,
+        format_line,
+    );
+    if annotate {
+        let misses: HashMap<_, _> = lines
+            .iter()
+            .map(|line| ((line.frame.file.as_deref(), line.frame.line), line.misses))
+            .collect();
+        for line in lines.iter().take(TOP_ENTRIES) {
+            display_annotated(&line.frame, &misses, workspace.as_deref());
+        }
+    }
+}
+
+/// Print a ranking as a table of the misses followed by the location, which is
+/// formatted via `location()`.
+fn display_ranking(title: &str, entries: &[Entry], location: fn(&Frame) -> String) {
+    if entries.is_empty() {
+        return;
+    }
+    eprintln!("{:>12} {title}", "Info".cyan().bold());
+    eprintln!(
+        "{:>12} {:>12} {:>8} {:>8} {:>8}",
+        "",
+        "Ir".bold(),
+        "I1".bold(),
+        "D1".bold(),
+        "LL".bold()
+    );
+    for entry in entries.iter().take(TOP_ENTRIES) {
+        let misses = entry.misses;
+        eprintln!(
+            "{:>12} {:>12} {:>8} {:>8} {:>8}  {}",
+            "",
+            misses.instructions,
+            misses.i1,
+            misses.d1,
+            misses.ll,
+            location(&entry.frame)
+        );
+    }
+}
+
+/// Format the location of a source line as `path:line`.
+///
+/// The path is shortened like in stack traces (see [`super::format_frame()`]).
+fn format_line(frame: &Frame) -> String {
+    let path = frame.path().unwrap_or_default();
+    let path = super::frame::display_path(
+        &path,
+        options::workspace().as_deref(),
+        options::sysroot().as_deref(),
+    );
+    format!("{}:{}", path.display(), frame.line.unwrap_or_default())
+}
+
+/// Show the source code around the line of the frame together with the misses
+/// of every line.
+fn display_annotated(
+    frame: &Frame,
+    misses: &HashMap<(Option<&str>, Option<usize>), Misses>,
+    workspace: Option<&Path>,
+) {
+    let Some((path, line)) = frame.path().zip(frame.line) else {
+        return;
+    };
+    let Some(snippet) = Snippet::read(&path, line) else {
+        return;
+    };
+    let shown_path = workspace
+        .and_then(|workspace| path.strip_prefix(workspace).ok())
+        .unwrap_or(&path);
+
+    let width = (snippet.first + snippet.lines.len() - 1).to_string().len();
+    eprintln!(
+        "{:>12} {:>39}{} {}:{line}",
+        "",
+        "",
+        "-->".blue().bold(),
+        shown_path.display()
+    );
+    for (number, code) in (snippet.first..).zip(&snippet.lines) {
+        let counts = misses
+            .get(&(frame.file.as_deref(), Some(number)))
+            .map_or_else(
+                || format!("{:39}", ""),
+                |misses| {
+                    format!(
+                        "{:>12} {:>8} {:>8} {:>8}",
+                        misses.instructions, misses.i1, misses.d1, misses.ll
+                    )
+                },
+            );
+        let gutter = format!("{number:>width$} |").blue().bold();
+        if number == snippet.line {
+            eprintln!("{:>12} {} {gutter} {}", "", counts.bold(), code.bold());
+        } else {
+            eprintln!("{:>12} {counts} {gutter} {code}", "");
+        }
+    }
+}
+
+/// Compute the misses per function sorted by the misses (most misses first).
+///
+/// Functions are identified by their name, since lines of inlined functions
+/// are attributed to the inlining function, but to the source file of the
+/// inlined one. The location of a function is the first source file seen.
+pub fn functions(cachegrind: &Cachegrind) -> Vec<Entry> {
+    let lines = cachegrind.lines.iter().map(|line| {
+        let frame = Frame {
+            line: None,
+            ..line.frame.clone()
+        };
+        (frame.function.clone(), frame, cachegrind.misses(&line.cost))
+    });
+    aggregate(lines)
+}
+
+/// Compute the misses per source line inside of the workspace sorted by the
+/// misses (most misses first).
+///
+/// Lines of different functions (e.g. of different instances of a generic
+/// function) are merged.
+pub fn workspace_lines(cachegrind: &Cachegrind, workspace: Option<&Path>) -> Vec<Entry> {
+    let lines = cachegrind
+        .lines
+        .iter()
+        .filter(|line| Origin::of(&line.frame, workspace) == Origin::Workspace)
+        .map(|line| {
+            let frame = Frame {
+                function: None,
+                ..line.frame.clone()
+            };
+            let key = (frame.file.clone(), frame.line);
+            (key, frame, cachegrind.misses(&line.cost))
+        });
+    aggregate(lines)
+}
+
+/// Sum up the misses of all locations with the same key and sort the result
+/// by the misses (most misses first).
+///
+/// The order is given by the last level misses, the first level data misses,
+/// the first level instruction misses and finally the instruction count, since
+/// the misses of the last level cache are the most expensive ones.
+fn aggregate<K, I>(locations: I) -> Vec<Entry>
+where
+    K: Eq + std::hash::Hash,
+    I: Iterator<Item = (K, Frame, Misses)>,
+{
+    let mut entries: Vec<Entry> = Vec::new();
+    let mut indices: HashMap<K, usize> = HashMap::new();
+    for (key, frame, misses) in locations {
+        let index = *indices.entry(key).or_insert(entries.len());
+        if index == entries.len() {
+            entries.push(Entry { frame, misses });
+        } else {
+            entries[index].misses += misses;
+        }
+    }
+    entries.sort_by_key(|entry| {
+        let misses = entry.misses;
+        std::cmp::Reverse((misses.ll, misses.d1, misses.i1, misses.instructions))
+    });
+    entries
+}
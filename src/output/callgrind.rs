@@ -0,0 +1,34 @@
+//! Summary of the call-graph profile recorded by callgrind.
+use crate::valgrind::callgrind::Callgrind;
+use colored::Colorize as _;
+use std::cmp::Ordering;
+
+/// Print the total cost of every recorded event.
+pub fn display(callgrind: &Callgrind) {
+    let mut events = callgrind.events.iter().zip(&callgrind.totals);
+    let Some((event, total)) = events.next() else {
+        eprintln!(
+            "{:>12} callgrind did not record any event",
+            "Info".cyan().bold()
+        );
+        return;
+    };
+    eprintln!("{:>12} {total} {event}", "Summary".cyan().bold());
+    for (event, total) in events {
+        eprintln!("{:>12} {total} {event}", "");
+    }
+}
+
+/// Print the change of the instruction count compared to the recorded one.
+pub fn display_change(recorded: u64, instructions: u64, change: f64) {
+    let change = format!("{change:+.2}%");
+    let change = match instructions.cmp(&recorded) {
+        Ordering::Greater => change.red().bold(),
+        Ordering::Less => change.green().bold(),
+        Ordering::Equal => change.normal(),
+    };
+    eprintln!(
+        "{:>12} {instructions} instructions compared to {recorded} recorded ({change})",
+        "Info".cyan().bold(),
+    );
+}
@@ -0,0 +1,142 @@
+//! Summary of the allocation profile recorded by DHAT.
+use super::frame::Origin;
+use crate::valgrind::dhat::Dhat;
+use crate::valgrind::xml::Frame;
+use colored::Colorize as _;
+use std::path::Path;
+
+/// The number of allocation sites shown per ranking.
+const TOP_SITES: usize = 5;
+
+/// An allocation site of the allocation profile.
+///
+/// This combines all program points, which are attributed to the same frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Site<'a> {
+    /// The frame allocating the memory or `None` if the stack trace is empty.
+    pub frame: Option<&'a Frame>,
+    /// The total number of bytes allocated.
+    pub bytes: u64,
+    /// The total number of blocks allocated.
+    pub blocks: u64,
+    /// The number of blocks, whose average lifetime is below the short-lived
+    /// threshold of DHAT.
+    pub short_lived_blocks: u64,
+    /// The number of bytes allocated in blocks, which were never read.
+    pub unread_bytes: u64,
+}
+
+/// Print the total allocations and the hottest allocation sites by total bytes,
+/// number of allocations and short-lived or never-read blocks.
+pub fn display(dhat: &Dhat) {
+    let bytes: u64 = dhat.program_points.iter().map(|pp| pp.total_bytes).sum();
+    let blocks: u64 = dhat.program_points.iter().map(|pp| pp.total_blocks).sum();
+    eprintln!(
+        "{:>12} allocated {} in {blocks} block{} at {} program point{}",
+        "Summary".cyan().bold(),
+        bytesize::ByteSize::b(bytes),
+        if blocks == 1 { "" } else { "s" },
+        dhat.program_points.len(),
+        if dhat.program_points.len() == 1 {
+            ""
+        } else {
+            "s"
+        },
+    );
+
+    let workspace = crate::options::workspace();
+    let mut sites = sites(dhat, workspace.as_deref());
+
+    sites.sort_by_key(|site| std::cmp::Reverse(site.bytes));
+    display_ranking("top allocation sites by total bytes", &sites, |site| {
+        (site.bytes > 0).then(|| bytesize::ByteSize::b(site.bytes).to_string())
+    });
+    sites.sort_by_key(|site| std::cmp::Reverse(site.blocks));
+    display_ranking("top allocation sites by number of blocks", &sites, |site| {
+        (site.blocks > 0).then(|| format!("{} blocks", site.blocks))
+    });
+    sites.sort_by_key(|site| std::cmp::Reverse(site.short_lived_blocks));
+    display_ranking("top sites of short-lived blocks", &sites, |site| {
+        (site.short_lived_blocks > 0).then(|| format!("{} blocks", site.short_lived_blocks))
+    });
+    if dhat.accesses_tracked {
+        sites.sort_by_key(|site| std::cmp::Reverse(site.unread_bytes));
+        display_ranking("top sites of never-read blocks", &sites, |site| {
+            (site.unread_bytes > 0).then(|| bytesize::ByteSize::b(site.unread_bytes).to_string())
+        });
+    }
+}
+
+/// Print the top sites of a ranking.
+///
+/// The `value` of a site is shown in front of its location. Sites without a
+/// value are not part of the ranking and the ranking is omitted completely, if
+/// no site has a value.
+fn display_ranking(title: &str, sites: &[Site], value: impl Fn(&Site) -> Option<String>) {
+    let mut ranking = sites
+        .iter()
+        .filter_map(|site| value(site).map(|value| (value, site)))
+        .take(TOP_SITES)
+        .peekable();
+    if ranking.peek().is_none() {
+        return;
+    }
+    eprintln!("{:>12} {title}", "Info".cyan().bold());
+    for (value, site) in ranking {
+        let location = site
+            .frame
+            .map_or_else(|| String::from("(unknown)"), super::format_frame);
+        eprintln!("{:>12} {value:>12}  {location}", "");
+    }
+}
+
+/// Compute the allocation sites of the profile in the order of their first
+/// occurrence.
+///
+/// The allocation functions, the standard library and the system libraries are
+/// not interesting as allocation sites, since they only allocate on behalf of
+/// their callers. Therefore each program point is attributed to its innermost
+/// frame outside of those (see [`Origin::is_noise()`]). Program points, whose
+/// frames are all noise, are attributed to their innermost frame.
+pub fn sites<'a>(dhat: &'a Dhat, workspace: Option<&Path>) -> Vec<Site<'a>> {
+    let mut sites: Vec<Site> = Vec::new();
+    for pp in &dhat.program_points {
+        let frame = pp
+            .frames
+            .iter()
+            .find(|frame| !Origin::of(frame, workspace).is_noise())
+            .or_else(|| pp.frames.first());
+        let short_lived_blocks = if pp.average_lifetime() < dhat.short_lived_threshold {
+            pp.total_blocks
+        } else {
+            0
+        };
+        let unread_bytes = if pp.read_bytes == Some(0) {
+            pp.total_bytes
+        } else {
+            0
+        };
+
+        let existing = sites.iter_mut().find(|site| match (site.frame, frame) {
+            (Some(a), Some(b)) => (&a.function, &a.file, a.line) == (&b.function, &b.file, b.line),
+            (None, None) => true,
+            _ => false,
+        });
+        match existing {
+            Some(site) => {
+                site.bytes += pp.total_bytes;
+                site.blocks += pp.total_blocks;
+                site.short_lived_blocks += short_lived_blocks;
+                site.unread_bytes += unread_bytes;
+            }
+            None => sites.push(Site {
+                frame,
+                bytes: pp.total_bytes,
+                blocks: pp.total_blocks,
+                short_lived_blocks,
+                unread_bytes,
+            }),
+        }
+    }
+    sites
+}
@@ -0,0 +1,216 @@
+//! Rendering of folded stacks as an interactive SVG flamegraph.
+//!
+//! The layout follows the flamegraphs of `flamegraph.pl`: every frame is a box,
+//! whose width is proportional to its cost, placed on top of its caller. The
+//! frames of one level are sorted alphabetically. Hovering over a frame shows
+//! its name and cost, clicking on it zooms into it and clicking on the bottom
+//! frame resets the zoom.
+use super::escape;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// The width of the image in pixels.
+const WIDTH: f64 = 1200.0;
+/// The horizontal padding of the image in pixels.
+const PADDING: f64 = 10.0;
+/// The width of the area of the frames in pixels.
+const INNER_WIDTH: f64 = WIDTH - 2.0 * PADDING;
+/// The height of a single frame in pixels.
+const FRAME_HEIGHT: usize = 16;
+/// The approximate width of a character of the labels in pixels.
+const CHARACTER_WIDTH: f64 = 7.0;
+/// The minimum width of a frame in pixels, narrower frames are omitted.
+const MIN_WIDTH: f64 = 0.1;
+
+/// The script implementing the zooming, which updates the position, width and
+/// label of every frame (see [`label()`] for the truncation of the labels).
+const SCRIPT: &str = r"
+var W = 1180, P = 10, C = 7;
+function label(n, w) {
+  var c = Math.floor((w - 6) / C);
+  return c < 3 ? '' : n.length <= c ? n : n.substring(0, c - 2) + '..';
+}
+function zoom(t) {
+  var x = +t.dataset.x, w = +t.dataset.w, d = +t.dataset.d, e = 1e-9;
+  var frames = document.querySelectorAll('g.f');
+  for (var i = 0; i < frames.length; i++) {
+    var f = frames[i], fx = +f.dataset.x, fw = +f.dataset.w, fd = +f.dataset.d;
+    var ancestor = fd < d && fx <= x + e && fx + fw >= x + w - e;
+    var inside = fd >= d && fx >= x - e && fx + fw <= x + w + e;
+    f.style.display = ancestor || inside ? '' : 'none';
+    if (!ancestor && !inside) continue;
+    var nx = ancestor ? 0 : (fx - x) / w, nw = ancestor ? W : fw / w * W;
+    var r = f.querySelector('rect'), s = f.querySelector('text');
+    r.setAttribute('x', P + nx * W);
+    r.setAttribute('width', Math.max(nw - 1, 0));
+    s.setAttribute('x', P + nx * W + 3);
+    s.textContent = label(f.dataset.n, nw);
+  }
+}
+";
+
+/// A frame of the flamegraph with all the frames called by it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Node<'a> {
+    /// The name of the frame.
+    name: &'a str,
+    /// The cost of the frame including all of its children.
+    value: u64,
+    /// The frames called by this frame, sorted by their name.
+    children: Vec<Self>,
+}
+impl<'a> Node<'a> {
+    /// Build the tree of the folded stacks below a root frame with the given
+    /// name.
+    fn tree(name: &'a str, stacks: &[(Vec<&'a str>, u64)]) -> Self {
+        let mut root = Node {
+            name,
+            value: 0,
+            children: Vec::new(),
+        };
+        for (stack, value) in stacks {
+            root.value += value;
+            let mut node = &mut root;
+            for name in stack {
+                let index = match node.children.binary_search_by(|child| child.name.cmp(name)) {
+                    Ok(index) => index,
+                    Err(index) => {
+                        node.children.insert(
+                            index,
+                            Node {
+                                name,
+                                value: 0,
+                                children: Vec::new(),
+                            },
+                        );
+                        index
+                    }
+                };
+                node = &mut node.children[index];
+                node.value += value;
+            }
+        }
+        root
+    }
+
+    /// The number of levels of the tree including this node.
+    fn depth(&self) -> usize {
+        1 + self.children.iter().map(Self::depth).max().unwrap_or(0)
+    }
+}
+
+/// Render the folded stacks as an SVG flamegraph with the given title.
+///
+/// Each stack consists of the names of its frames (outermost first) and its
+/// cost, which is described by `unit` (e.g. `instructions`).
+pub fn svg(title: &str, stacks: &[(Vec<&str>, u64)], unit: &str) -> String {
+    let root = Node::tree("all", stacks);
+    let height = root.depth() * FRAME_HEIGHT + 3 * FRAME_HEIGHT;
+
+    let mut svg = String::new();
+    writeln!(
+        svg,
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" version="1.1" width="{WIDTH}" height="{height}" viewBox="0 0 {WIDTH} {height}" font-family="Verdana, sans-serif" font-size="12">
+<style>g.f:hover rect {{ stroke: black; stroke-width: 0.5; }} g.f {{ cursor: pointer; }}</style>
+<script><![CDATA[{SCRIPT}]]></script>
+<rect width="100%" height="100%" fill="rgb(250,250,238)"/>
+<text x="{}" y="24" text-anchor="middle" font-size="17">{}</text>"#,
+        WIDTH / 2.0,
+        escape(title),
+    )
+    .expect("writing to a string never fails");
+    if root.value > 0 {
+        let frame = Frame {
+            total: root.value,
+            unit,
+            bottom: height - FRAME_HEIGHT,
+        };
+        frame.write(&mut svg, &root, 0, 0);
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// Render the folded stacks as an SVG flamegraph and write it to `file`.
+///
+/// # Errors
+/// Any I/O error during writing the file is returned.
+pub fn write(file: &Path, title: &str, stacks: &[(Vec<&str>, u64)], unit: &str) -> io::Result<()> {
+    fs::write(file, svg(title, stacks, unit))
+}
+
+/// The parameters for rendering the frames.
+struct Frame<'a> {
+    /// The cost of the root frame, which spans the whole width.
+    total: u64,
+    /// The unit of the costs.
+    unit: &'a str,
+    /// The vertical position of the bottom of the root frame.
+    bottom: usize,
+}
+impl Frame<'_> {
+    /// Write the node at the given horizontal `offset` (as a cost) and `depth`
+    /// together with all of its children.
+    #[allow(clippy::cast_precision_loss)] // only used for the layout
+    fn write(&self, svg: &mut String, node: &Node, offset: u64, depth: usize) {
+        let position = offset as f64 / self.total as f64;
+        let share = node.value as f64 / self.total as f64;
+        let width = share * INNER_WIDTH;
+        if width < MIN_WIDTH {
+            return;
+        }
+        let left = position.mul_add(INNER_WIDTH, PADDING);
+        let top = self.bottom - (depth + 1) * FRAME_HEIGHT;
+        let (red, green, blue) = color(node.name);
+        let name = escape(node.name);
+        writeln!(
+            svg,
+            r#"<g class="f" data-x="{position}" data-w="{share}" data-d="{depth}" data-n="{name}" onclick="zoom(this)"><title>{name} ({} {}, {:.2}%)</title><rect x="{left:.1}" y="{top}" width="{:.1}" height="{}" rx="2" fill="rgb({red},{green},{blue})"/><text x="{:.1}" y="{}">{}</text></g>"#,
+            node.value,
+            self.unit,
+            share * 100.0,
+            (width - 1.0).max(0.0),
+            FRAME_HEIGHT - 1,
+            left + 3.0,
+            top + FRAME_HEIGHT - 4,
+            escape(&label(node.name, width)),
+        )
+        .expect("writing to a string never fails");
+
+        let mut offset = offset;
+        for child in &node.children {
+            self.write(svg, child, offset, depth + 1);
+            offset += child.value;
+        }
+    }
+}
+
+/// Truncate the name to fit into a frame of the given width in pixels.
+///
+/// Names are shortened with `..` and omitted completely, if less than three
+/// characters would fit.
+fn label(name: &str, width: f64) -> String {
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)] // clamped
+    let characters = ((width - 6.0) / CHARACTER_WIDTH).max(0.0).floor() as usize;
+    if characters < 3 {
+        String::new()
+    } else if name.chars().count() <= characters {
+        name.to_string()
+    } else {
+        let truncated: String = name.chars().take(characters - 2).collect();
+        truncated + ".."
+    }
+}
+
+/// Compute a warm color for the frame, which only depends on its name, so that
+/// the same function has the same color in every flamegraph.
+fn color(name: &str) -> (u64, u64, u64) {
+    // FNV-1a
+    let hash = name.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    });
+    (205 + hash % 50, 80 + (hash >> 8) % 150, (hash >> 16) % 55)
+}
@@ -0,0 +1,66 @@
+//! Output of the leaked memory as folded stacks for flamegraph tools.
+//!
+//! Every line consists of the frames of a leak's stack trace joined by `;`
+//! (outermost frame first) followed by the number of leaked bytes, e.g.
+//! `main;my_crate::run;alloc::alloc::alloc 64`. This is the input format of
+//! e.g. `inferno-flamegraph` or `flamegraph.pl`, which visualize where the
+//! leaked memory was allocated. Identical stacks are merged.
+use super::Report;
+use crate::valgrind::xml::{Error, Frame};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Write the leaks of all analyzed binaries as folded stacks to `file`.
+///
+/// Leaks known from the baseline are included, since they leak memory as well.
+///
+/// # Errors
+/// Any I/O error during writing the file is returned.
+pub fn write(file: &Path, reports: &[Report]) -> io::Result<()> {
+    let errors = reports
+        .iter()
+        .flat_map(|report| report.errors.iter().chain(&report.known_errors));
+    let mut folded = String::new();
+    for (stack, bytes) in stacks(errors) {
+        writeln!(folded, "{stack} {bytes}").expect("writing to a string never fails");
+    }
+    fs::write(file, folded)
+}
+
+/// Compute the folded stacks of the leaks among the errors together with the
+/// leaked bytes, sorted by the stacks.
+///
+/// Errors, that are not leaks, and leaks without a stack trace are skipped.
+pub fn stacks<'a, I: IntoIterator<Item = &'a Error>>(errors: I) -> BTreeMap<String, usize> {
+    let mut stacks = BTreeMap::new();
+    for error in errors.into_iter().filter(|error| error.kind.is_leak()) {
+        let Some(stack) = error.stack_trace.first() else {
+            continue;
+        };
+        if stack.frames.is_empty() {
+            continue;
+        }
+        let stack: Vec<_> = stack.frames.iter().rev().map(name).collect();
+        *stacks.entry(stack.join(";")).or_default() += error.resources.bytes;
+    }
+    stacks
+}
+
+/// The name of a frame inside of a folded stack.
+///
+/// This is the function name or the file name of the object for unknown
+/// functions. Semicolons (e.g. of array types like `[u8; 4]`) are replaced,
+/// since they separate the frames.
+fn name(frame: &Frame) -> String {
+    let name = frame.function.as_deref().unwrap_or_else(|| {
+        frame
+            .object
+            .as_deref()
+            .and_then(|object| object.rsplit('/').next())
+            .unwrap_or("unknown")
+    });
+    name.replace(';', ",")
+}
@@ -0,0 +1,174 @@
+//! Classification of stack frames by their origin.
+//!
+//! Most frames of a typical stack trace are not interesting to the user: the
+//! allocation functions of valgrind, the allocator of the Rust standard library
+//! or the runtime calling `main()`. Those frames are collapsed by default, so
+//! that the frames of the user code stand out.
+use crate::valgrind::xml::{Frame, Stack};
+use std::path::{Component, Path, PathBuf};
+
+/// The origin of a stack frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Origin {
+    /// The functions replaced by valgrind, e.g. `malloc()` or `free()`.
+    Valgrind,
+    /// The Rust standard library (`std`, `core` and `alloc`).
+    Std,
+    /// System libraries like the C library or the dynamic loader.
+    System,
+    /// A dependency from a registry or a git repository.
+    Dependency,
+    /// The code of the workspace, that cargo operates on.
+    Workspace,
+    /// Anything, that could not be classified.
+    Other,
+}
+impl Origin {
+    /// Classify a frame by its object file, source directory and function.
+    pub fn of(frame: &Frame, workspace: Option<&Path>) -> Self {
+        let object = frame.object.as_deref().unwrap_or_default();
+        let directory = frame.directory.as_deref().map(Path::new);
+        let function = frame.function.as_deref().unwrap_or_default();
+
+        if object.contains("vgpreload_") || frame.file.as_deref() == Some("vg_replace_malloc.c") {
+            Self::Valgrind
+        } else if workspace
+            .is_some_and(|workspace| frame.path().is_some_and(|path| path.starts_with(workspace)))
+        {
+            Self::Workspace
+        } else if directory.is_some_and(is_std_directory) {
+            Self::Std
+        } else if directory.is_some_and(is_dependency_directory) {
+            Self::Dependency
+        } else if is_std_function(function) {
+            Self::Std
+        } else if ["/lib/", "/lib64/", "/usr/lib/", "/usr/lib64/"]
+            .iter()
+            .any(|prefix| object.starts_with(prefix))
+        {
+            Self::System
+        } else {
+            Self::Other
+        }
+    }
+
+    /// Query, whether frames of this origin are hidden by default.
+    pub const fn is_noise(self) -> bool {
+        match self {
+            Self::Valgrind | Self::Std | Self::System => true,
+            Self::Dependency | Self::Workspace | Self::Other => false,
+        }
+    }
+}
+
+/// A line of a (possibly collapsed) stack trace.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Line<'a> {
+    /// A frame, that is shown.
+    Frame(&'a Frame, Origin),
+    /// A number of consecutive frames, that are hidden.
+    Hidden(usize),
+}
+
+/// Split the stack trace into the lines to display.
+///
+/// Unless `full` is set, consecutive noise frames (see [`Origin::is_noise()`])
+/// are collapsed into a single [`Line::Hidden`]. If every frame is noise, the
+/// whole stack is shown nevertheless, since there would be nothing left.
+pub fn lines<'a>(stack: &'a Stack, workspace: Option<&Path>, full: bool) -> Vec<Line<'a>> {
+    let frames: Vec<_> = stack
+        .frames
+        .iter()
+        .map(|frame| (frame, Origin::of(frame, workspace)))
+        .collect();
+    let show_all = full || frames.iter().all(|(_, origin)| origin.is_noise());
+
+    let mut lines = Vec::with_capacity(frames.len());
+    for (frame, origin) in frames {
+        match lines.last_mut() {
+            _ if show_all || !origin.is_noise() => lines.push(Line::Frame(frame, origin)),
+            Some(Line::Hidden(hidden)) => *hidden += 1,
+            _ => lines.push(Line::Hidden(1)),
+        }
+    }
+    lines
+}
+
+/// Check, if the source directory belongs to the Rust standard library.
+///
+/// This is either the remapped path of the pre-built standard library
+/// (`/rustc/<commit>/…`) or the `rust-src` component of a toolchain.
+fn is_std_directory(directory: &Path) -> bool {
+    directory.starts_with("/rustc")
+        || directory
+            .to_string_lossy()
+            .contains("/lib/rustlib/src/rust/")
+}
+
+/// Check, if the function belongs to the Rust standard library.
+///
+/// This is used for frames without (or with only relative) source directories,
+/// e.g. if the standard library was built without debug information.
+fn is_std_function(function: &str) -> bool {
+    let function = function.trim_start_matches('<');
+    ["std::", "core::", "alloc::", "__rust"]
+        .iter()
+        .any(|prefix| function.starts_with(prefix))
+}
+
+/// Check, if the source directory belongs to a dependency, which is either
+/// downloaded from a registry or checked out from a git repository.
+fn is_dependency_directory(directory: &Path) -> bool {
+    dependency_path(directory).is_some()
+}
+
+/// Shorten the path of a source file for displaying it to the user.
+///
+/// - paths inside of the workspace are made relative to the workspace root,
+/// - paths of the pre-built standard library (`/rustc/<commit>/…`) are remapped
+///   to the `rust-src` component of the `sysroot`, if it is installed,
+/// - paths of dependencies start at the directory of the dependency, e.g.
+///   `libc-0.2.150/src/lib.rs` instead of the full path inside of the registry.
+///
+/// Other paths are returned unchanged.
+pub fn display_path(path: &Path, workspace: Option<&Path>, sysroot: Option<&Path>) -> PathBuf {
+    if let Some(relative) = workspace.and_then(|workspace| path.strip_prefix(workspace).ok()) {
+        return relative.to_path_buf();
+    }
+    if let Some(local) = sysroot.and_then(|sysroot| std_source(path, sysroot)) {
+        return local;
+    }
+    dependency_path(path).unwrap_or_else(|| path.to_path_buf())
+}
+
+/// Remap a path of the pre-built standard library (`/rustc/<commit>/…`) to the
+/// `rust-src` component of the `sysroot`, if the file exists there.
+fn std_source(path: &Path, sysroot: &Path) -> Option<PathBuf> {
+    let mut components = path.strip_prefix("/rustc").ok()?.components();
+    components.next()?; // the commit hash
+    let local = sysroot
+        .join("lib/rustlib/src/rust")
+        .join(components.as_path());
+    local.exists().then_some(local)
+}
+
+/// The path of a dependency relative to the registry or git checkout directory.
+///
+/// For registry dependencies, this starts at the directory of the crate, e.g.
+/// `<cargo home>/registry/src/<index>/libc-0.2.150/src` is turned into
+/// `libc-0.2.150/src`. For git dependencies, this starts at the directory of
+/// the repository, i.e. `<cargo home>/git/checkouts/<repository>/…`.
+fn dependency_path(path: &Path) -> Option<PathBuf> {
+    let components: Vec<_> = path.components().map(Component::as_os_str).collect();
+    let index = components
+        .windows(2)
+        .position(|window| window == ["registry", "src"] || window == ["git", "checkouts"])?;
+    // skip the index directory of registries
+    let skip = if components[index] == "registry" {
+        3
+    } else {
+        2
+    };
+    let dependency: PathBuf = components.get(index + skip..)?.iter().collect();
+    (dependency.components().count() > 0).then_some(dependency)
+}
@@ -0,0 +1,69 @@
+//! Annotations for GitHub Actions.
+//!
+//! Every error is printed as an `::error` (or `::warning`) [workflow command]
+//! to stdout, which GitHub shows inline in the diff of a pull request. The
+//! annotation is anchored at the topmost frame inside of the workspace.
+//!
+//! [workflow command]: https://docs.github.com/en/actions/reference/workflows-and-actions/workflow-commands
+use super::Report;
+use crate::valgrind::xml::{Error, Kind};
+use std::env;
+use std::path::{Path, PathBuf};
+
+/// Print a workflow command for every error of the report to stdout.
+///
+/// File paths are written relative to the repository checked out by GitHub
+/// (`GITHUB_WORKSPACE`), falling back to the cargo `workspace` otherwise.
+pub fn display_report(report: &Report, workspace: Option<&Path>) {
+    let repository = env::var_os("GITHUB_WORKSPACE").map(PathBuf::from);
+    for error in &report.errors {
+        println!("{}", annotation(error, workspace, repository.as_deref()));
+    }
+}
+
+/// Create the workflow command for a single error.
+fn annotation(error: &Error, workspace: Option<&Path>, repository: Option<&Path>) -> String {
+    let command = match error.kind {
+        Kind::LeakStillReachable | Kind::LeakPossiblyLost => "warning",
+        _ => "error",
+    };
+    let mut properties = vec![format!(
+        "title={}",
+        escape_property(&error.kind.to_string())
+    )];
+    let frame = error
+        .stack_trace
+        .first()
+        .and_then(|stack| super::workspace_frame(stack, workspace));
+    if let Some((frame, path)) = frame.and_then(|frame| Some((frame, frame.path()?))) {
+        let path = repository
+            .or(workspace)
+            .and_then(|base| path.strip_prefix(base).ok())
+            .unwrap_or(&path);
+        properties.push(format!("file={}", escape_property(&path.to_string_lossy())));
+        if let Some(line) = frame.line {
+            properties.push(format!("line={line}"));
+        }
+    }
+
+    let message = format!("{}\n{}", super::describe(error), super::render(error));
+    format!(
+        "::{command} {}::{}",
+        properties.join(","),
+        escape_data(message.trim_end())
+    )
+}
+
+/// Escape the message of a workflow command.
+fn escape_data(data: &str) -> String {
+    data.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+/// Escape a property value of a workflow command.
+fn escape_property(property: &str) -> String {
+    escape_data(property)
+        .replace(':', "%3A")
+        .replace(',', "%2C")
+}
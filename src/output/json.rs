@@ -0,0 +1,22 @@
+//! Machine-readable JSON output.
+//!
+//! Every analyzed binary results in a single [`Report`] serialized as one line
+//! of JSON on stdout, so that the output of multiple binaries (e.g. when running
+//! `cargo valgrind test`) forms a JSON-lines stream. The reports are printed
+//! after cargo finished, so that they are not interleaved with the output of the
+//! programs under test.
+use super::Report;
+use std::io::{self, Write as _};
+
+/// Write every report as a single line of JSON to stdout.
+///
+/// # Errors
+/// Any I/O error during writing to stdout is returned.
+pub fn display_reports(reports: &[Report]) -> io::Result<()> {
+    let mut stdout = io::stdout().lock();
+    for report in reports {
+        serde_json::to_writer(&mut stdout, report).map_err(io::Error::other)?;
+        writeln!(stdout)?;
+    }
+    stdout.flush()
+}
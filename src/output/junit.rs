@@ -0,0 +1,115 @@
+//! Output in the `JUnit` XML format, so that CI systems can show the valgrind
+//! findings next to the normal test results.
+//!
+//! Every analyzed binary is a test suite. Each error found in a binary is a
+//! failed test case containing the stack traces. Binaries without any errors
+//! contain a single passing test case.
+use super::{escape, Report};
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Write the reports of all analyzed binaries as a `JUnit` XML file to `file`.
+///
+/// # Errors
+/// Any I/O error during writing the file is returned.
+pub fn write(file: &Path, reports: &[Report]) -> io::Result<()> {
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let tests: usize = reports.iter().map(test_cases).sum();
+    let failures: usize = reports.iter().map(failures).sum();
+    writeln!(
+        xml,
+        r#"<testsuites name="cargo valgrind" tests="{tests}" failures="{failures}">"#
+    )
+    .expect("writing to a string never fails");
+    for report in reports {
+        write_suite(&mut xml, report);
+    }
+    xml.push_str("</testsuites>\n");
+
+    fs::write(file, xml)
+}
+
+/// Write the test suite of a single binary.
+fn write_suite(xml: &mut String, report: &Report) {
+    let name = suite_name(report);
+    writeln!(
+        xml,
+        r#"  <testsuite name="{}" tests="{}" failures="{}">"#,
+        escape(&name),
+        test_cases(report),
+        failures(report),
+    )
+    .expect("writing to a string never fails");
+
+    for (index, error) in report.errors.iter().enumerate() {
+        writeln!(
+            xml,
+            r#"    <testcase classname="{}" name="{}">"#,
+            escape(&name),
+            escape(&format!(
+                "{} #{}: {}",
+                error.kind,
+                index + 1,
+                super::describe(error)
+            )),
+        )
+        .expect("writing to a string never fails");
+        writeln!(
+            xml,
+            r#"      <failure type="{}" message="{}">{}</failure>"#,
+            escape(&error.kind.to_string()),
+            escape(&super::describe(error)),
+            escape(&super::render(error)),
+        )
+        .expect("writing to a string never fails");
+        xml.push_str("    </testcase>\n");
+    }
+    if report.errors.is_empty() {
+        writeln!(
+            xml,
+            r#"    <testcase classname="{}" name="valgrind">"#,
+            escape(&name)
+        )
+        .expect("writing to a string never fails");
+        if let Some(signal) = report.signal {
+            writeln!(
+                xml,
+                r#"      <failure type="signal" message="the program was terminated by signal {signal}"/>"#
+            )
+            .expect("writing to a string never fails");
+        }
+        xml.push_str("    </testcase>\n");
+    }
+
+    xml.push_str("  </testsuite>\n");
+}
+
+/// The name of the test suite of a binary.
+///
+/// This is the file name of the binary (or a placeholder if unknown).
+fn suite_name(report: &Report) -> String {
+    report
+        .binary
+        .as_deref()
+        .map(Path::new)
+        .and_then(Path::file_name)
+        .map_or_else(
+            || String::from("unknown"),
+            |name| name.to_string_lossy().into_owned(),
+        )
+}
+
+/// The number of test cases in the suite of a binary.
+fn test_cases(report: &Report) -> usize {
+    report.errors.len().max(1)
+}
+
+/// The number of failed test cases in the suite of a binary.
+fn failures(report: &Report) -> usize {
+    match report.errors.len() {
+        0 => usize::from(report.signal.is_some()),
+        errors => errors,
+    }
+}
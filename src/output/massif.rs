@@ -0,0 +1,117 @@
+//! Summary of the heap profile recorded by massif.
+use super::frame::Origin;
+use crate::valgrind::massif::{Massif, Node};
+use crate::valgrind::xml::Frame;
+use colored::Colorize as _;
+use std::path::Path;
+
+/// The number of allocation sites shown in the summary.
+const TOP_SITES: usize = 10;
+
+/// An allocation site of the heap profile.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Site<'a> {
+    /// The frame allocating the memory or `None` for the summary of all
+    /// insignificant sites.
+    pub frame: Option<&'a Frame>,
+    /// The number of bytes allocated at the peak.
+    pub bytes: u64,
+}
+
+/// Print the peak heap usage and the top allocation sites at the peak.
+pub fn display(massif: &Massif) {
+    let Some(peak) = massif.peak() else {
+        eprintln!(
+            "{:>12} massif did not record any snapshot",
+            "Info".cyan().bold()
+        );
+        return;
+    };
+    eprintln!(
+        "{:>12} peak heap usage of {} (+{} overhead) at snapshot {}",
+        "Summary".cyan().bold(),
+        bytesize::ByteSize::b(peak.heap),
+        bytesize::ByteSize::b(peak.heap_extra),
+        peak.index,
+    );
+
+    let workspace = crate::options::workspace();
+    let sites = peak
+        .tree
+        .as_ref()
+        .map(|tree| top_sites(tree, workspace.as_deref()))
+        .unwrap_or_default();
+    if sites.is_empty() {
+        return;
+    }
+    eprintln!(
+        "{:>12} top allocation sites at the peak",
+        "Info".cyan().bold()
+    );
+    for site in sites.iter().take(TOP_SITES) {
+        let percentage = percentage(site.bytes, peak.heap);
+        let location = site.frame.map_or_else(
+            || String::from("(below the threshold of massif)"),
+            super::format_frame,
+        );
+        eprintln!(
+            "{:>12} {:>10} {percentage:>5.1}%  {location}",
+            "",
+            bytesize::ByteSize::b(site.bytes).to_string(),
+        );
+    }
+}
+
+/// Compute the allocation sites of the allocation tree sorted by the number of
+/// allocated bytes (largest first).
+///
+/// The allocation functions, the standard library and the system libraries are
+/// not interesting as allocation sites, since they only allocate on behalf of
+/// their callers. Therefore the bytes of those frames are attributed to their
+/// first caller outside of those (see [`Origin::is_noise()`]).
+pub fn top_sites<'a>(tree: &'a Node, workspace: Option<&Path>) -> Vec<Site<'a>> {
+    let mut sites: Vec<Site> = Vec::new();
+    collect_sites(tree, workspace, &mut sites);
+    sites.sort_by_key(|site| std::cmp::Reverse(site.bytes));
+    sites
+}
+
+/// Collect the allocation sites of the node into `sites`, merging sites with
+/// the same function and source location.
+fn collect_sites<'a>(node: &'a Node, workspace: Option<&Path>, sites: &mut Vec<Site<'a>>) {
+    let is_noise = node
+        .frame
+        .as_ref()
+        .map_or(true, |frame| Origin::of(frame, workspace).is_noise());
+    if is_noise && !node.children.is_empty() {
+        for child in &node.children {
+            collect_sites(child, workspace, sites);
+        }
+        return;
+    }
+
+    let existing = sites
+        .iter_mut()
+        .find(|site| match (site.frame, &node.frame) {
+            (Some(a), Some(b)) => (&a.function, &a.file, a.line) == (&b.function, &b.file, b.line),
+            (None, None) => true,
+            _ => false,
+        });
+    match existing {
+        Some(site) => site.bytes += node.bytes,
+        None => sites.push(Site {
+            frame: node.frame.as_ref(),
+            bytes: node.bytes,
+        }),
+    }
+}
+
+/// Compute the percentage of `part` of `total`.
+#[allow(clippy::cast_precision_loss)] // only used for displaying
+fn percentage(part: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        part as f64 * 100.0 / total as f64
+    }
+}
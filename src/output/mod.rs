@@ -0,0 +1,481 @@
+//! Write human-readable and colored output the the console.
+//!
+//! Machine-readable formats are provided by the submodules.
+pub mod cachegrind;
+pub mod callgrind;
+pub mod dhat;
+pub mod flamegraph;
+pub mod folded;
+mod frame;
+pub mod github;
+pub mod json;
+pub mod junit;
+pub mod massif;
+pub mod sarif;
+mod snippet;
+
+#[cfg(test)]
+mod tests;
+
+use crate::{options, valgrind};
+use colored::Colorize as _;
+use serde::{Deserialize, Serialize};
+use std::fmt::Write as _;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::{fs, io, process};
+
+/// The result of analyzing a single binary.
+///
+/// This is the data made available in the machine-readable output formats.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "reason", rename = "valgrind-report")]
+pub struct Report {
+    /// The version of the report schema.
+    pub version: u32,
+    /// The executed binary (the first argument passed to the runner).
+    pub binary: Option<String>,
+    /// The exit code of `cargo valgrind` for this binary.
+    pub exit_code: i32,
+    /// The signal, which terminated the program under test, if any.
+    pub signal: Option<i32>,
+    /// All the errors detected by valgrind, which are not part of the baseline.
+    pub errors: Vec<valgrind::xml::Error>,
+    /// The errors detected by valgrind, which are known from the baseline.
+    /// Repeated errors with the same fingerprint might be left out.
+    #[serde(default)]
+    pub known_errors: Vec<valgrind::xml::Error>,
+    /// The number of executed instructions (only measured by callgrind).
+    #[serde(default)]
+    pub instructions: Option<u64>,
+}
+impl Report {
+    /// The current version of the report schema.
+    ///
+    /// This is incremented on incompatible changes to the schema.
+    pub const VERSION: u32 = 1;
+
+    /// Create an empty report for the given binary.
+    pub const fn new(binary: Option<String>) -> Self {
+        Self {
+            version: Self::VERSION,
+            binary,
+            exit_code: 0,
+            signal: None,
+            errors: Vec::new(),
+            known_errors: Vec::new(),
+            instructions: None,
+        }
+    }
+
+    /// Store the report as a new JSON file inside of the given directory.
+    ///
+    /// The file name starts with the current time, so that [`load_all()`] can
+    /// return the reports in the order they were created.
+    ///
+    /// # Errors
+    /// Any I/O error during writing the file is returned.
+    ///
+    /// [`load_all()`]: Self::load_all
+    pub fn save(&self, directory: &Path) -> io::Result<()> {
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        let file = directory.join(format!("{time:020}-{}.json", process::id()));
+        fs::write(file, serde_json::to_vec(self).map_err(io::Error::other)?)
+    }
+
+    /// Load all reports stored in the given directory via [`save()`].
+    ///
+    /// # Errors
+    /// Any I/O error or a malformed report file is returned as an error.
+    ///
+    /// [`save()`]: Self::save
+    pub fn load_all(directory: &Path) -> io::Result<Vec<Self>> {
+        let mut files = fs::read_dir(directory)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<io::Result<Vec<_>>>()?;
+        files.sort();
+        files
+            .into_iter()
+            .filter(|file| {
+                file.extension()
+                    .is_some_and(|extension| extension == "json")
+            })
+            .map(|file| {
+                let report = fs::read(file)?;
+                serde_json::from_slice(&report).map_err(io::Error::other)
+            })
+            .collect()
+    }
+}
+
+/// Print the report in the requested formats in addition to the human-readable
+/// output.
+pub fn display_formats(report: &Report) {
+    if options::github_annotations() {
+        github::display_report(report, options::workspace().as_deref());
+    }
+}
+
+/// Nicely format the errors in the valgrind output, if there are any.
+pub fn display_errors(errors: &[valgrind::xml::Error]) {
+    for error in errors {
+        display_error(error);
+    }
+    display_summary(errors);
+}
+
+/// Nicely format a single error, e.g. as soon as valgrind reported it.
+pub fn display_error(error: &valgrind::xml::Error) {
+    if error.kind.is_leak() || error.kind.is_fd_leak() {
+        display_leak(error);
+    } else {
+        display_generic_error(error);
+    }
+}
+
+/// Summarize the errors after they were displayed.
+pub fn display_summary(errors: &[valgrind::xml::Error]) {
+    let total: usize = errors.iter().map(|error| error.resources.bytes).sum();
+    let fds = errors.iter().filter(|e| e.kind.is_fd_leak()).count();
+    let fds = match fds {
+        0 => String::new(),
+        1 => String::from(" and 1 file descriptor"),
+        n => format!(" and {n} file descriptors"),
+    };
+    eprintln!(
+        "{:>12} Leaked {}{fds} total ({} other errors)",
+        "Summary".red().bold(),
+        bytesize::ByteSize::b(total as _),
+        errors
+            .iter()
+            .filter(|e| !e.kind.is_leak() && !e.kind.is_fd_leak())
+            .count()
+    );
+}
+
+/// Describe an error in a single line.
+///
+/// Leaks are described by the amount of leaked memory, leaked file descriptors
+/// by the file or socket and other errors by the main information provided by
+/// valgrind. Errors of traced child processes name the process as well.
+pub fn describe(error: &valgrind::xml::Error) -> String {
+    let description = describe_error(error);
+    match &error.process {
+        Some(process) => format!(
+            "{description} (in child process {} `{}`)",
+            describe_pid(process.pid),
+            process.command
+        ),
+        None => description,
+    }
+}
+
+/// Describe an error in a single line regardless of its process.
+fn describe_error(error: &valgrind::xml::Error) -> String {
+    if error.kind.is_leak() {
+        format!(
+            "leaked {} in {} block{}",
+            bytesize::ByteSize::b(error.resources.bytes as _),
+            error.resources.blocks,
+            if error.resources.blocks == 1 { "" } else { "s" }
+        )
+    } else if error.kind.is_fd_leak() {
+        // valgrind describes the descriptor as e.g. `Open file descriptor 3:
+        // /tmp/file` or `Open AF_INET socket 4: 127.0.0.1:80 <-> unbound`
+        let descriptor = error.main_info.as_deref().map_or_else(
+            || {
+                let fd = error.fd.map(|fd| format!(" {fd}")).unwrap_or_default();
+                format!("file descriptor{fd}")
+            },
+            |info| info.strip_prefix("Open ").unwrap_or(info).to_string(),
+        );
+        format!("leaked {descriptor}")
+    } else {
+        let info = error
+            .main_info
+            .clone()
+            .unwrap_or_else(|| error.kind.to_string());
+        match &error.thread_name {
+            Some(thread) => format!("{info} (in thread '{thread}')"),
+            None => info,
+        }
+    }
+}
+
+/// Render the stack traces of an error as plain text.
+///
+/// This is similar to the human-readable report, but without any formatting,
+/// so that it can be embedded into other formats.
+fn render(error: &valgrind::xml::Error) -> String {
+    let mut text = String::new();
+    for (index, stack) in error.stack_trace.iter().enumerate() {
+        let message = match index {
+            0 => "stack trace (user code at the bottom)",
+            _ => error
+                .auxiliary_info
+                .get(index - 1)
+                .map_or("additional stack trace", String::as_str),
+        };
+        writeln!(text, "{message}").expect("writing to a string never fails");
+        for line in stack_lines(stack) {
+            match line {
+                frame::Line::Frame(frame, _) => writeln!(text, "    at {}", format_frame(frame)),
+                frame::Line::Hidden(hidden) => writeln!(text, "    {}", describe_hidden(hidden)),
+            }
+            .expect("writing to a string never fails");
+        }
+    }
+    for info in unpaired_auxiliary_info(error) {
+        writeln!(text, "{info}").expect("writing to a string never fails");
+    }
+    text
+}
+
+/// The auxiliary information of an error, which does not describe a stack
+/// trace, but e.g. the accessed address.
+fn unpaired_auxiliary_info(error: &valgrind::xml::Error) -> &[String] {
+    let paired = error.stack_trace.len().saturating_sub(1);
+    error.auxiliary_info.get(paired..).unwrap_or_default()
+}
+
+/// Search the topmost frame of a stack trace inside of the workspace.
+fn workspace_frame<'a>(
+    stack: &'a valgrind::xml::Stack,
+    workspace: Option<&Path>,
+) -> Option<&'a valgrind::xml::Frame> {
+    let workspace = workspace?;
+    stack
+        .frames
+        .iter()
+        .find(|frame| frame.path().is_some_and(|path| path.starts_with(workspace)))
+}
+
+/// Escape the characters, that have a special meaning in XML (and SVG).
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Warn about an `--error-exitcode` in the `VALGRINDFLAGS`, which is replaced
+/// by the exit code of `cargo valgrind` for memory errors.
+pub fn display_ignored_error_exitcode(code: &str) {
+    eprintln!(
+        "{}: `--error-exitcode={code}` in VALGRINDFLAGS is ignored, memory errors are always reported with exit code 127",
+        "warning".yellow().bold()
+    );
+}
+
+/// Warn about a valgrind XML output of a protocol version newer than the
+/// supported ones, which is only parsed on a best-effort basis.
+pub fn display_newer_protocol_version(version: u32) {
+    eprintln!(
+        "{}: valgrind uses the unknown XML protocol version {version}, the results might be incomplete",
+        "warning".yellow().bold()
+    );
+}
+
+/// List the traced child processes of the program together with the number of
+/// errors found in each of them.
+pub fn display_child_processes(children: &[valgrind::xml::Output]) {
+    for child in children {
+        let errors = child.errors.as_ref().map_or(0, Vec::len);
+        let process = valgrind::xml::Process::of(child);
+        eprintln!(
+            "{:>12} child process {} `{}`: {errors} error{}",
+            "Traced".cyan().bold(),
+            describe_pid(process.pid),
+            process.command,
+            if errors == 1 { "" } else { "s" }
+        );
+    }
+}
+
+/// Describe an optional process id.
+fn describe_pid(pid: Option<u32>) -> String {
+    pid.map_or_else(|| String::from("?"), |pid| pid.to_string())
+}
+
+/// Inform about the number of errors, that were ignored since they are known
+/// from the baseline.
+pub fn display_known_errors(count: usize) {
+    match count {
+        0 => {}
+        1 => eprintln!(
+            "{:>12} 1 known error from the baseline was ignored",
+            "Info".cyan().bold()
+        ),
+        n => eprintln!(
+            "{:>12} {n} known errors from the baseline were ignored",
+            "Info".cyan().bold()
+        ),
+    }
+}
+
+/// Inform about the baseline file, that was written.
+pub fn display_baseline_written(file: &Path, errors: usize) {
+    eprintln!(
+        "{:>12} baseline `{}` with {errors} known error{}",
+        "Wrote".green().bold(),
+        file.display(),
+        if errors == 1 { "" } else { "s" }
+    );
+}
+
+/// Inform about the results file, that was written.
+pub fn display_results_written(file: &Path, binaries: usize) {
+    eprintln!(
+        "{:>12} results `{}` with the instruction counts of {binaries} binar{}",
+        "Wrote".green().bold(),
+        file.display(),
+        if binaries == 1 { "y" } else { "ies" }
+    );
+}
+
+/// Inform about the flamegraph, that was written.
+pub fn display_flamegraph_written(file: &Path) {
+    eprintln!(
+        "{:>12} flamegraph `{}`",
+        "Wrote".green().bold(),
+        file.display()
+    );
+}
+
+/// Report the errors of the baseline, that were not observed anymore.
+pub fn display_fixed_errors(fingerprints: &[&str]) {
+    for fingerprint in fingerprints {
+        eprintln!("{:>12} {fingerprint}", "Fixed".green().bold());
+    }
+    if !fingerprints.is_empty() {
+        eprintln!(
+            "{:>12} remove the fixed errors from the baseline or regenerate it via `--write-baseline`",
+            "Info".cyan().bold(),
+        );
+    }
+}
+
+/// Nicely format a single memory leak error.
+fn display_leak(error: &valgrind::xml::Error) {
+    eprintln!("{:>12} {}", "Error".red().bold(), describe(error));
+
+    let stack = &error.stack_trace[0]; // always available
+    display_stack_trace("stack trace (user code at the bottom)", stack);
+    display_snippet(stack);
+}
+
+/// Nicely format a non-memory-leak error.
+fn display_generic_error(error: &valgrind::xml::Error) {
+    eprintln!("{:>12} {}", "Error".red().bold(), describe(error));
+
+    // unknown error kinds might come without any stack trace
+    if let Some(stack) = error.stack_trace.first() {
+        display_stack_trace("main stack trace (user code at the bottom)", stack);
+        display_snippet(stack);
+    }
+    error
+        .stack_trace
+        .iter()
+        .skip(1)
+        .enumerate()
+        .map(|(index, stack)| (error.auxiliary_info.get(index), stack))
+        .for_each(|(msg, stack)| {
+            display_stack_trace(
+                msg.map_or_else(|| "additional stack trace", String::as_str),
+                stack,
+            );
+        });
+    for info in unpaired_auxiliary_info(error) {
+        eprintln!("{:>12} {info}", "Info".cyan().bold());
+    }
+}
+
+/// Write out the full stack trace (indented to match other messages).
+fn display_stack_trace(msg: &str, stack: &valgrind::xml::Stack) {
+    eprintln!("{:>12} {}", "Info".cyan().bold(), msg);
+    for line in stack_lines(stack) {
+        match line {
+            frame::Line::Frame(frame, frame::Origin::Workspace) => {
+                eprintln!("             at {}", format_frame(frame).bold());
+            }
+            frame::Line::Frame(frame, _) => eprintln!("             at {}", format_frame(frame)),
+            frame::Line::Hidden(hidden) => {
+                eprintln!("             {}", describe_hidden(hidden).dimmed());
+            }
+        }
+    }
+}
+
+/// Show the source code of the topmost frame of the stack inside of the
+/// workspace, if there is one.
+fn display_snippet(stack: &valgrind::xml::Stack) {
+    let workspace = options::workspace();
+    if let Some(frame) = workspace_frame(stack, workspace.as_deref()) {
+        snippet::display(frame, workspace.as_deref());
+    }
+}
+
+/// Split a stack trace into the lines to display, collapsing the noise frames
+/// unless the full stacks are requested.
+fn stack_lines(stack: &valgrind::xml::Stack) -> Vec<frame::Line<'_>> {
+    frame::lines(
+        stack,
+        options::workspace().as_deref(),
+        options::full_stacks(),
+    )
+}
+
+/// Describe a number of hidden frames.
+fn describe_hidden(hidden: usize) -> String {
+    format!(
+        "… {hidden} frame{} hidden",
+        if hidden == 1 { "" } else { "s" }
+    )
+}
+
+/// Format a single frame of a stack trace as `function (path:line)`.
+///
+/// The path is shortened (see [`frame::display_path()`]) and generic arguments
+/// are elided if requested.
+fn format_frame(frame: &valgrind::xml::Frame) -> String {
+    let mut text = match &frame.function {
+        Some(function) if options::elide_generics() => valgrind::symbol::elide_generics(function),
+        Some(function) => function.clone(),
+        None => String::from("unknown"),
+    };
+    if let Some(path) = frame.path() {
+        let path = frame::display_path(
+            &path,
+            options::workspace().as_deref(),
+            options::sysroot().as_deref(),
+        );
+        write!(text, " ({}", path.display()).expect("writing to a string never fails");
+        if let Some(line) = frame.line {
+            write!(text, ":{line}").expect("writing to a string never fails");
+        }
+        text.push(')');
+    }
+    text
+}
+
+/// Write out an error message for describing the stack overflow message.
+pub fn display_stack_overflow(output: &str) {
+    let error = "Error".red().bold();
+    let info = "Info".cyan().bold();
+    eprintln!("{error:>12}: looks like the program overflowed its stack");
+    eprintln!("{info:>12}: valgrind says:");
+    output
+        .lines()
+        .for_each(|line| eprintln!("              {line}"));
+}
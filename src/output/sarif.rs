@@ -0,0 +1,214 @@
+//! Output in the [SARIF 2.1.0][sarif] format for code-scanning dashboards.
+//!
+//! All analyzed binaries are combined into a single SARIF run. Each error kind
+//! is mapped to a rule and each error to a result, whose primary location is
+//! the first frame in user code. The remaining stack traces are added as
+//! related locations and all stack traces are provided as code flows.
+//!
+//! [sarif]: https://docs.oasis-open.org/sarif/sarif/v2.1.0/sarif-v2.1.0.html
+use super::Report;
+use crate::valgrind::xml::{Error, Frame, Kind, Stack};
+use serde_json::{json, Value};
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// The URI base identifier used for paths inside of the workspace.
+const SOURCE_ROOT: &str = "%SRCROOT%";
+
+/// Write the reports of all analyzed binaries as a SARIF log to `file`.
+///
+/// Source locations inside of the `workspace` are written relative to it, so
+/// that dashboards can map them to the repository contents.
+///
+/// # Errors
+/// Any I/O error during writing the file is returned.
+pub fn write(file: &Path, reports: &[Report], workspace: Option<&Path>) -> io::Result<()> {
+    let mut kinds = Vec::new();
+    let mut results = Vec::new();
+    for report in reports {
+        for error in &report.errors {
+            let rule_index = kinds.iter().position(|kind| *kind == error.kind);
+            let rule_index = rule_index.unwrap_or_else(|| {
+                kinds.push(error.kind.clone());
+                kinds.len() - 1
+            });
+            results.push(result(error, rule_index, report, workspace));
+        }
+    }
+
+    let mut run = json!({
+        "tool": {
+            "driver": {
+                "name": env!("CARGO_PKG_NAME"),
+                "version": env!("CARGO_PKG_VERSION"),
+                "informationUri": env!("CARGO_PKG_REPOSITORY"),
+                "rules": kinds.iter().map(rule).collect::<Vec<_>>(),
+            }
+        },
+        "results": results,
+    });
+    if let Some(workspace) = workspace {
+        run["originalUriBaseIds"] = json!({
+            SOURCE_ROOT: { "uri": format!("{}/", to_uri(workspace).trim_end_matches('/')) }
+        });
+    }
+    let log = json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [run],
+    });
+
+    fs::write(
+        file,
+        serde_json::to_vec_pretty(&log).map_err(io::Error::other)?,
+    )
+}
+
+/// Create the SARIF rule describing an error kind.
+fn rule(kind: &Kind) -> Value {
+    json!({
+        "id": kind.id(),
+        "shortDescription": { "text": kind.to_string() },
+        "defaultConfiguration": { "level": level(kind) },
+    })
+}
+
+/// Create the SARIF result for a single valgrind error.
+fn result(error: &Error, rule_index: usize, report: &Report, workspace: Option<&Path>) -> Value {
+    let mut result = json!({
+        "ruleId": error.kind.id(),
+        "ruleIndex": rule_index,
+        "level": level(&error.kind),
+        "message": { "text": super::describe(error) },
+        "codeFlows": [{
+            "threadFlows": error
+                .stack_trace
+                .iter()
+                .enumerate()
+                .map(|(index, stack)| thread_flow(error, index, stack, workspace))
+                .collect::<Vec<_>>(),
+        }],
+        "properties": {
+            "binary": report.binary,
+            "leakedBytes": error.resources.bytes,
+            "leakedBlocks": error.resources.blocks,
+        },
+    });
+
+    let primary = error
+        .stack_trace
+        .first()
+        .and_then(|stack| user_frame(stack, workspace))
+        .and_then(|frame| location(frame, workspace));
+    if let Some(primary) = primary {
+        result["locations"] = json!([primary]);
+    }
+
+    let related = error
+        .stack_trace
+        .iter()
+        .enumerate()
+        .skip(1)
+        .filter_map(|(index, stack)| {
+            let mut location = location(user_frame(stack, workspace)?, workspace)?;
+            location["id"] = json!(index);
+            location["message"] = json!({ "text": stack_description(error, index) });
+            Some(location)
+        })
+        .collect::<Vec<_>>();
+    if !related.is_empty() {
+        result["relatedLocations"] = json!(related);
+    }
+
+    result
+}
+
+/// Create a SARIF thread flow for a single stack trace of an error.
+///
+/// The locations are ordered from the innermost frame to the outermost one, as
+/// in the valgrind output.
+fn thread_flow(error: &Error, index: usize, stack: &Stack, workspace: Option<&Path>) -> Value {
+    let locations = stack
+        .frames
+        .iter()
+        .map(|frame| {
+            let mut location = location(frame, workspace).unwrap_or_else(|| json!({}));
+            location["message"] = json!({ "text": frame.to_string() });
+            json!({ "location": location })
+        })
+        .collect::<Vec<_>>();
+    json!({
+        "message": { "text": stack_description(error, index) },
+        "locations": locations,
+    })
+}
+
+/// Describe the stack trace with the given index of an error.
+fn stack_description(error: &Error, index: usize) -> String {
+    match index {
+        0 => String::from("main stack trace"),
+        _ => error
+            .auxiliary_info
+            .get(index - 1)
+            .map_or_else(|| String::from("additional stack trace"), String::clone),
+    }
+}
+
+/// Search the frame, that should be reported as the location of an error.
+///
+/// This is the topmost frame inside of the workspace, or, if there is none,
+/// the topmost frame with a known source file.
+fn user_frame<'a>(stack: &'a Stack, workspace: Option<&Path>) -> Option<&'a Frame> {
+    super::workspace_frame(stack, workspace)
+        .or_else(|| stack.frames.iter().find(|frame| frame.file.is_some()))
+}
+
+/// Create a SARIF location of the given frame, if its source file is known.
+fn location(frame: &Frame, workspace: Option<&Path>) -> Option<Value> {
+    let path = frame.path()?;
+    let artifact = workspace
+        .and_then(|workspace| path.strip_prefix(workspace).ok())
+        .map_or_else(
+            || json!({ "uri": to_uri(&path) }),
+            |relative| json!({ "uri": to_uri(relative), "uriBaseId": SOURCE_ROOT }),
+        );
+    let mut location = json!({ "physicalLocation": { "artifactLocation": artifact } });
+    if let Some(line) = frame.line {
+        location["physicalLocation"]["region"] = json!({ "startLine": line });
+    }
+    if let Some(function) = &frame.function {
+        location["logicalLocations"] = json!([{ "fullyQualifiedName": function }]);
+    }
+    Some(location)
+}
+
+/// Convert a path into a (percent-encoded) URI.
+///
+/// Absolute paths are converted to `file://` URIs, relative paths are kept as
+/// relative references.
+fn to_uri(path: &Path) -> String {
+    let path = path.to_string_lossy();
+    let mut uri = String::from(if path.starts_with('/') { "file://" } else { "" });
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                uri.push(char::from(byte));
+            }
+            _ => write!(uri, "%{byte:02X}").expect("writing to a string never fails"),
+        }
+    }
+    uri
+}
+
+/// The SARIF level of an error kind.
+///
+/// Leaks, which might not be actual leaks, are reported as warnings, all other
+/// errors as errors.
+const fn level(kind: &Kind) -> &'static str {
+    match kind {
+        Kind::LeakStillReachable | Kind::LeakPossiblyLost => "warning",
+        _ => "error",
+    }
+}
@@ -0,0 +1,81 @@
+//! Source code snippets of the user code, similar to the diagnostics of rustc.
+use crate::valgrind::xml::Frame;
+use colored::Colorize as _;
+use std::fs;
+use std::path::Path;
+
+/// The number of lines shown before and after the line of the frame.
+const CONTEXT: usize = 2;
+
+/// The lines of a source file surrounding a certain line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snippet {
+    /// The line number of the first line of the snippet (starting at 1).
+    pub first: usize,
+    /// The line number of the line, that is pointed at.
+    pub line: usize,
+    /// The source code lines of the snippet.
+    pub lines: Vec<String>,
+}
+impl Snippet {
+    /// Read the snippet around the `line` from the source `file`.
+    ///
+    /// `None` is returned, if the file is not readable or does not contain the
+    /// line.
+    pub fn read(file: &Path, line: usize) -> Option<Self> {
+        if line == 0 {
+            return None;
+        }
+        let source = fs::read_to_string(file).ok()?;
+        let first = line
+            .checked_sub(CONTEXT)
+            .filter(|&first| first > 0)
+            .unwrap_or(1);
+        let lines: Vec<String> = source
+            .lines()
+            .skip(first - 1)
+            .take(line + CONTEXT + 1 - first)
+            .map(|line| line.trim_end().replace('\t', "    "))
+            .collect();
+        (first + lines.len() > line).then_some(Self { first, line, lines })
+    }
+}
+
+/// Show the source code of the frame (if available) with a caret underlining
+/// the offending line.
+///
+/// The path is shown relative to the workspace.
+pub fn display(frame: &Frame, workspace: Option<&Path>) {
+    let Some((path, line)) = frame.path().zip(frame.line) else {
+        return;
+    };
+    let Some(snippet) = Snippet::read(&path, line) else {
+        return;
+    };
+    let shown_path = workspace
+        .and_then(|workspace| path.strip_prefix(workspace).ok())
+        .unwrap_or(&path);
+
+    let width = (snippet.first + snippet.lines.len() - 1).to_string().len();
+    let gutter = |number: &str| format!("{:>12} {number:>width$} |", "").blue().bold();
+    eprintln!(
+        "{:>12} {:>width$}{} {}:{line}",
+        "",
+        "",
+        "-->".blue().bold(),
+        shown_path.display()
+    );
+    eprintln!("{}", gutter(""));
+    for (number, code) in (snippet.first..).zip(&snippet.lines) {
+        if code.is_empty() {
+            eprintln!("{}", gutter(&number.to_string()));
+        } else {
+            eprintln!("{} {code}", gutter(&number.to_string()));
+        }
+        if number == snippet.line {
+            let indentation = code.len() - code.trim_start().len();
+            let carets = "^".repeat(code.trim().chars().count().max(1));
+            eprintln!("{} {:indentation$}{}", gutter(""), "", carets.red().bold());
+        }
+    }
+}
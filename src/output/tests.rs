@@ -0,0 +1,409 @@
+use super::Report;
+use crate::valgrind::xml::Error;
+use serde::Deserialize;
+use serde_json::Value;
+use std::path::Path;
+use std::{env, fs, process};
+
+/// An invalid read inside of the workspace `/project` with an auxiliary stack.
+fn invalid_read() -> Error {
+    let xml = "<error>\
+           <unique>0x1</unique>\
+           <tid>1</tid>\
+           <kind>InvalidRead</kind>\
+           <what>Invalid read of size 4</what>\
+           <stack>\
+             <frame>\
+               <ip>0x10</ip>\
+               <obj>/usr/lib/libc.so</obj>\
+               <fn>memcpy</fn>\
+             </frame>\
+             <frame>\
+               <ip>0x20</ip>\
+               <dir>/project/src</dir>\
+               <fn>project::main</fn>\
+               <file>main.rs</file>\
+               <line>7</line>\
+             </frame>\
+           </stack>\
+           <auxwhat>Address 0x0 is not stack'd, malloc'd or (recently) free'd</auxwhat>\
+           <stack>\
+             <frame>\
+               <ip>0x30</ip>\
+               <dir>/rustc/abc/library/alloc/src</dir>\
+               <fn>alloc::alloc::alloc</fn>\
+               <file>alloc.rs</file>\
+               <line>95</line>\
+             </frame>\
+           </stack>\
+         </error>";
+    Error::deserialize(
+        &mut serde_xml_rs::Deserializer::new_from_reader(xml.as_bytes())
+            .non_contiguous_seq_elements(true),
+    )
+    .expect("Could not parse test XML")
+}
+
+/// A report of a single binary containing the given errors.
+fn report(errors: Vec<Error>) -> Report {
+    let mut report = Report::new(Some("/project/target/debug/project".into()));
+    report.exit_code = 127;
+    report.errors = errors;
+    report
+}
+
+#[test]
+fn reports_survive_a_roundtrip_through_the_report_directory() {
+    let directory = env::temp_dir().join(format!("cargo-valgrind-test-{}", process::id()));
+    fs::create_dir_all(&directory).expect("Could not create directory");
+    report(vec![invalid_read()])
+        .save(&directory)
+        .expect("Could not save report");
+    report(Vec::new())
+        .save(&directory)
+        .expect("Could not save report");
+
+    let reports = Report::load_all(&directory);
+    fs::remove_dir_all(&directory).ok();
+    let reports = reports.expect("Could not load reports");
+
+    assert_eq!(reports.len(), 2);
+    assert_eq!(reports[0].errors, vec![invalid_read()]);
+    assert_eq!(reports[0].exit_code, 127);
+    assert!(reports[1].errors.is_empty());
+}
+
+#[test]
+fn sarif_uses_first_workspace_frame_as_location() {
+    let file = env::temp_dir().join(format!("cargo-valgrind-test-{}.sarif", process::id()));
+    super::sarif::write(
+        &file,
+        &[report(vec![invalid_read()])],
+        Some(Path::new("/project")),
+    )
+    .expect("Could not write SARIF");
+    let sarif = fs::read(&file);
+    fs::remove_file(&file).ok();
+    let sarif: Value =
+        serde_json::from_slice(&sarif.expect("Could not read SARIF")).expect("SARIF is valid JSON");
+
+    let run = &sarif["runs"][0];
+    assert_eq!(sarif["version"], "2.1.0");
+    assert_eq!(
+        run["originalUriBaseIds"]["%SRCROOT%"]["uri"],
+        "file:///project/"
+    );
+    assert_eq!(run["tool"]["driver"]["rules"][0]["id"], "InvalidRead");
+
+    let result = &run["results"][0];
+    assert_eq!(result["ruleId"], "InvalidRead");
+    assert_eq!(result["message"]["text"], "Invalid read of size 4");
+    let location = &result["locations"][0]["physicalLocation"];
+    assert_eq!(location["artifactLocation"]["uri"], "src/main.rs");
+    assert_eq!(location["artifactLocation"]["uriBaseId"], "%SRCROOT%");
+    assert_eq!(location["region"]["startLine"], 7);
+
+    let related = &result["relatedLocations"][0];
+    assert_eq!(
+        related["physicalLocation"]["artifactLocation"]["uri"],
+        "file:///rustc/abc/library/alloc/src/alloc.rs"
+    );
+    assert_eq!(
+        related["message"]["text"],
+        "Address 0x0 is not stack'd, malloc'd or (recently) free'd"
+    );
+    assert_eq!(
+        result["codeFlows"][0]["threadFlows"]
+            .as_array()
+            .map(Vec::len),
+        Some(2)
+    );
+}
+
+#[test]
+fn junit_contains_a_suite_per_binary_and_a_case_per_error() {
+    let file = env::temp_dir().join(format!("cargo-valgrind-test-{}.xml", process::id()));
+    let mut clean = report(Vec::new());
+    clean.binary = Some("/project/target/debug/deps/clean-0123".into());
+    super::junit::write(&file, &[report(vec![invalid_read()]), clean])
+        .expect("Could not write JUnit report");
+    let junit = fs::read_to_string(&file);
+    fs::remove_file(&file).ok();
+    let junit = junit.expect("Could not read JUnit report");
+
+    assert!(junit.contains(r#"<testsuites name="cargo valgrind" tests="2" failures="1">"#));
+    assert!(junit.contains(r#"<testsuite name="project" tests="1" failures="1">"#));
+    assert!(junit.contains(r#"<testsuite name="clean-0123" tests="1" failures="0">"#));
+    assert!(junit.contains(r#"message="Invalid read of size 4""#));
+    assert!(junit.contains("    at project::main (/project/src/main.rs:7)"));
+    assert!(junit.contains("Address 0x0 is not stack&apos;d"));
+}
+
+#[test]
+fn frames_are_classified_by_their_origin() {
+    use super::frame::Origin;
+
+    let error = invalid_read();
+    let workspace = Some(Path::new("/project"));
+    let origins: Vec<_> = error
+        .stack_trace
+        .iter()
+        .flat_map(|stack| &stack.frames)
+        .map(|frame| Origin::of(frame, workspace))
+        .collect();
+    assert_eq!(origins, [Origin::System, Origin::Workspace, Origin::Std]);
+}
+
+#[test]
+fn noise_frames_are_collapsed() {
+    use super::frame::{lines, Line, Origin};
+
+    let error = invalid_read();
+    let workspace = Some(Path::new("/project"));
+    let stack = &error.stack_trace[0];
+    assert_eq!(
+        lines(stack, workspace, false),
+        [
+            Line::Hidden(1),
+            Line::Frame(&stack.frames[1], Origin::Workspace)
+        ]
+    );
+    assert_eq!(lines(stack, workspace, true).len(), 2);
+
+    // a stack consisting only of noise is shown completely
+    let stack = &error.stack_trace[1];
+    assert_eq!(
+        lines(stack, workspace, false),
+        [Line::Frame(&stack.frames[0], Origin::Std)]
+    );
+}
+
+#[test]
+fn snippets_contain_the_surrounding_lines() {
+    use super::snippet::Snippet;
+
+    let file = env::temp_dir().join(format!("cargo-valgrind-snippet-{}.rs", process::id()));
+    fs::write(&file, "fn main() {\n\tlet x = 1;\n    leak(x);\n}\n").expect("could not write file");
+
+    let snippet = Snippet::read(&file, 2).expect("line is part of the file");
+    assert_eq!(snippet.first, 1);
+    assert_eq!(
+        snippet.lines,
+        ["fn main() {", "    let x = 1;", "    leak(x);", "}"]
+    );
+
+    assert!(Snippet::read(&file, 5).is_none());
+    assert!(Snippet::read(&file, 0).is_none());
+    fs::remove_file(file).ok();
+}
+
+#[test]
+fn paths_are_shortened_for_display() {
+    use super::frame::display_path;
+
+    let workspace = Some(Path::new("/project"));
+    assert_eq!(
+        display_path(Path::new("/project/src/main.rs"), workspace, None),
+        Path::new("src/main.rs")
+    );
+    assert_eq!(
+        display_path(
+            Path::new("/home/user/.cargo/registry/src/index.crates.io-6f17d22bba15001f/libc-0.2.150/src/lib.rs"),
+            workspace,
+            None
+        ),
+        Path::new("libc-0.2.150/src/lib.rs")
+    );
+    assert_eq!(
+        display_path(
+            Path::new("/home/user/.cargo/git/checkouts/vk-0123456789abcdef/1a2b3c4/src/lib.rs"),
+            workspace,
+            None
+        ),
+        Path::new("vk-0123456789abcdef/1a2b3c4/src/lib.rs")
+    );
+    assert_eq!(
+        display_path(Path::new("/usr/include/stdio.h"), workspace, None),
+        Path::new("/usr/include/stdio.h")
+    );
+}
+
+#[test]
+fn std_paths_are_remapped_to_the_sysroot() {
+    use super::frame::display_path;
+
+    let sysroot = env::temp_dir().join(format!("cargo-valgrind-sysroot-{}", process::id()));
+    let source = sysroot.join("lib/rustlib/src/rust/library/alloc/src");
+    fs::create_dir_all(&source).expect("could not create sysroot");
+    fs::write(source.join("alloc.rs"), "").expect("could not create source file");
+
+    let path = Path::new("/rustc/0123456789abcdef/library/alloc/src/alloc.rs");
+    assert_eq!(
+        display_path(path, None, Some(&sysroot)),
+        source.join("alloc.rs")
+    );
+    let missing = Path::new("/rustc/0123456789abcdef/library/core/src/ptr.rs");
+    assert_eq!(display_path(missing, None, Some(&sysroot)), missing);
+    fs::remove_dir_all(sysroot).ok();
+}
+
+#[test]
+fn massif_allocations_are_attributed_to_the_first_user_frame() {
+    let massif =
+        fs::read_to_string("src/valgrind/massif/massif.out").expect("Could not open test file");
+    let massif = crate::valgrind::massif::parse(&massif).expect("Could not read test file");
+    let peak = massif
+        .peak()
+        .and_then(|peak| peak.tree.as_ref())
+        .expect("detailed peak");
+
+    let sites = super::massif::top_sites(peak, None);
+    let sites: Vec<_> = sites
+        .iter()
+        .map(|site| {
+            (
+                site.frame.and_then(|frame| frame.function.as_deref()),
+                site.bytes,
+            )
+        })
+        .collect();
+    assert_eq!(
+        sites,
+        [
+            (Some("service::cache::new"), 4096),
+            (Some("service::load"), 2048),
+            (None, 1000),
+            (None, 24)
+        ]
+    );
+}
+
+#[test]
+fn dhat_program_points_are_merged_by_the_first_user_frame() {
+    let dhat = fs::read_to_string("src/valgrind/dhat/dhat.out").expect("Could not open test file");
+    let dhat = crate::valgrind::dhat::parse(&dhat).expect("Could not read test file");
+
+    let sites = super::dhat::sites(&dhat, None);
+    let sites: Vec<_> = sites
+        .iter()
+        .map(|site| {
+            (
+                site.frame.and_then(|frame| frame.function.as_deref()),
+                site.bytes,
+                site.blocks,
+                site.short_lived_blocks,
+                site.unread_bytes,
+            )
+        })
+        .collect();
+    assert_eq!(
+        sites,
+        [
+            (Some("parser::token::push"), 67000, 1600, 1600, 0),
+            (Some("parser::buffer::Buffer::new"), 4096, 1, 0, 4096),
+        ]
+    );
+}
+
+#[test]
+fn cachegrind_misses_are_ranked_per_function_and_workspace_line() {
+    let cachegrind = fs::read_to_string("src/valgrind/cachegrind/cachegrind.out")
+        .expect("Could not open test file");
+    let cachegrind =
+        crate::valgrind::cachegrind::parse(&cachegrind).expect("Could not read test file");
+
+    let functions: Vec<_> = super::cachegrind::functions(&cachegrind)
+        .into_iter()
+        .map(|entry| (entry.frame.function, entry.misses.ll))
+        .collect();
+    assert_eq!(
+        functions,
+        [
+            (Some(String::from("matrix::multiply")), 53),
+            (Some(String::from("alloc::vec::Vec<T,A>::push")), 13),
+            (Some(String::from("matrix::main")), 5),
+            (None, 5),
+        ]
+    );
+
+    let lines = super::cachegrind::workspace_lines(&cachegrind, Some(Path::new("/project")));
+    let lines: Vec<_> = lines
+        .iter()
+        .map(|entry| (entry.frame.line, entry.misses.d1))
+        .collect();
+    assert_eq!(lines, [(Some(11), 800), (Some(10), 125), (Some(20), 2)]);
+    assert!(super::cachegrind::workspace_lines(&cachegrind, None).is_empty());
+}
+
+#[test]
+fn flamegraph_contains_a_frame_per_function_of_each_stack() {
+    let stacks = [
+        (vec!["main", "parse<T>"], 75),
+        (vec!["main"], 25),
+        (vec!["main", "parse<T>", "tiny"], 0),
+    ];
+    let svg = super::flamegraph::svg("Instructions of `prog`", &stacks, "instructions");
+    assert!(svg.starts_with("<?xml"));
+    assert!(svg.trim_end().ends_with("</svg>"));
+    assert!(svg.contains("<title>all (100 instructions, 100.00%)</title>"));
+    assert!(svg.contains("<title>main (100 instructions, 100.00%)</title>"));
+    assert!(svg.contains("<title>parse&lt;T&gt; (75 instructions, 75.00%)</title>"));
+    assert!(!svg.contains("tiny"));
+    assert_eq!(svg.matches("<g class=\"f\"").count(), 3);
+}
+
+#[test]
+fn leaks_are_folded_into_stacks_weighted_by_bytes() {
+    let xml = fs::read("src/valgrind/xml/version4.xml").expect("Could not open test file");
+    let xml = crate::valgrind::parse(&xml).expect("Could not read test file");
+    let errors = xml.errors.expect("There are errors in the test case");
+
+    let stacks = super::folded::stacks(&errors);
+    let leaked: usize = errors.iter().map(|error| error.resources.bytes).sum();
+    assert_eq!(stacks.values().sum::<usize>(), leaked);
+    assert_eq!(
+        stacks.get(
+            "vkCreateInstance;libvulkan.so.1.1.115;dlopen;libdl-2.29.so;_dl_catch_error;\
+             _dl_catch_exception;libdl-2.29.so;_dl_open;_dl_catch_exception;dl_open_worker;\
+             _dl_check_map_versions;calloc"
+        ),
+        Some(&1416)
+    );
+    assert!(stacks.keys().all(|stack| ["malloc", "calloc", "realloc"]
+        .iter()
+        .any(|allocator| stack.ends_with(allocator))));
+}
+
+#[test]
+fn leaked_file_descriptors_are_described_by_their_file_or_socket() {
+    let xml = fs::read("src/valgrind/xml/fds.xml").expect("Could not open test file");
+    let xml = crate::valgrind::parse(&xml).expect("Could not read test file");
+    let errors = xml.errors.expect("There are errors in the test case");
+
+    assert_eq!(
+        super::describe(&errors[0]),
+        "File descriptor 3: /tmp/daemon.log is already closed"
+    );
+    assert_eq!(
+        super::describe(&errors[1]),
+        "leaked AF_INET socket 4: 127.0.0.1:41234 <-> 127.0.0.1:8080"
+    );
+    assert_eq!(
+        super::describe(&errors[2]),
+        "leaked file descriptor 5: /project/state.db"
+    );
+}
+
+#[test]
+fn errors_of_child_processes_name_the_process() {
+    let mut error = invalid_read();
+    error.process = Some(crate::valgrind::xml::Process {
+        pid: Some(4242),
+        ppid: Some(4241),
+        command: "/project/target/debug/helper --serve".into(),
+    });
+    assert_eq!(
+        super::describe(&error),
+        "Invalid read of size 4 (in child process 4242 `/project/target/debug/helper --serve`)"
+    );
+}
@@ -0,0 +1,186 @@
+//! Running the profiling tools of valgrind and evaluating their results.
+//!
+//! In contrast to the error detectors, the profilers write their results into
+//! an output file, which is parsed and summarized after the program finished.
+//! Budgets (e.g. `--max-heap`) and regressions of recorded results (e.g. of the
+//! instruction count) let the run fail like a memory error does.
+use crate::archive::Archive;
+use crate::options::{self, Tool};
+use crate::output;
+use crate::results::{self, Results};
+use crate::valgrind::{self, Exit};
+use colored::Colorize as _;
+use std::ffi::OsStr;
+use std::io;
+use std::path::Path;
+
+/// Run the command inside of the selected profiling `tool`.
+///
+/// The exit code to use for the process is returned, which is `127` if a
+/// budget was exceeded. Measurements of the profile are stored in the report,
+/// as well as the signal, which terminated the program (if any).
+pub fn run<S, I>(
+    tool: Tool,
+    command: I,
+    archive: Option<&Archive>,
+    report: &mut output::Report,
+) -> i32
+where
+    S: AsRef<OsStr>,
+    I: IntoIterator<Item = S>,
+{
+    let (profile, exit) = match valgrind::profile(tool, command, archive) {
+        Ok(profile) => profile,
+        Err(valgrind::Error::StackOverflow(output)) => {
+            output::display_stack_overflow(&output);
+            return 134; // default exit code for stack overflows
+        }
+        Err(e) => {
+            eprintln!("{}: {e}", "error".red().bold());
+            return 1;
+        }
+    };
+    let profile = String::from_utf8_lossy(&profile);
+
+    let result = match tool {
+        Tool::Massif => massif(&profile),
+        Tool::Dhat => dhat(&profile),
+        Tool::Callgrind => callgrind(&profile, report),
+        Tool::Cachegrind => cachegrind(&profile),
+        Tool::Memcheck | Tool::Helgrind | Tool::Drd => unreachable!("not a profiler"),
+    };
+    match result {
+        Ok(exit_code) if exit == Exit::Code(0) => exit_code,
+        // a failure of the program itself (e.g. a failing test) takes
+        // precedence over the budgets and regression gates
+        Ok(_) => match exit {
+            Exit::Code(code) => {
+                eprintln!(
+                    "{}: the program exited with code {code}",
+                    "info".cyan().bold()
+                );
+                code
+            }
+            Exit::Signal(signal_nr) => {
+                eprintln!(
+                    "{}: the program was terminated by signal {signal_nr}",
+                    "info".cyan().bold()
+                );
+                report.signal = Some(signal_nr);
+                128 + signal_nr
+            }
+        },
+        Err(e) => {
+            eprintln!(
+                "{}: unexpected output of {}: {e}",
+                "error".red().bold(),
+                tool.name()
+            );
+            1
+        }
+    }
+}
+
+/// Summarize the heap profile and check the heap budget.
+fn massif(profile: &str) -> Result<i32, String> {
+    let massif = valgrind::massif::parse(profile)?;
+    output::massif::display(&massif);
+
+    let peak = massif.peak().map_or(0, |peak| peak.heap);
+    match options::max_heap() {
+        Some(budget) if peak > budget.as_u64() => {
+            eprintln!(
+                "{:>12} peak heap usage of {} exceeds the budget of {budget}",
+                "Error".red().bold(),
+                bytesize::ByteSize::b(peak),
+            );
+            Ok(127)
+        }
+        _ => Ok(0),
+    }
+}
+
+/// Summarize the allocation profile.
+fn dhat(profile: &str) -> Result<i32, String> {
+    let dhat = valgrind::dhat::parse(profile)?;
+    output::dhat::display(&dhat);
+    Ok(0)
+}
+
+/// Summarize the cache profile.
+fn cachegrind(profile: &str) -> Result<i32, String> {
+    let cachegrind = valgrind::cachegrind::parse(profile)?;
+    output::cachegrind::display(&cachegrind, options::annotate());
+    Ok(0)
+}
+
+/// Summarize the call-graph profile and compare the instruction count to the
+/// recorded results.
+fn callgrind(profile: &str, report: &mut output::Report) -> Result<i32, String> {
+    let callgrind = valgrind::callgrind::parse(profile)?;
+    output::callgrind::display(&callgrind);
+    if options::flamegraph() {
+        write_flamegraph(&callgrind, report.binary.as_deref().unwrap_or_default());
+    }
+    let instructions = callgrind
+        .total("Ir")
+        .ok_or("the instruction count was not recorded")?;
+    report.instructions = Some(instructions);
+
+    let Some(file) = options::results_file() else {
+        return Ok(0);
+    };
+    if options::write_results() {
+        return Ok(0);
+    }
+    let name = results::name(report.binary.as_deref().unwrap_or_default());
+    let Some(recorded) = Results::load(&file)
+        .ok()
+        .and_then(|results| results.get(&name))
+    else {
+        eprintln!(
+            "{:>12} no instruction count of `{name}` is recorded in `{}`",
+            "Info".cyan().bold(),
+            file.display()
+        );
+        return Ok(0);
+    };
+    let change = results::change(recorded, instructions);
+    output::callgrind::display_change(recorded, instructions, change);
+    let max_regression = options::max_regression();
+    if change > max_regression {
+        eprintln!(
+            "{:>12} instruction count regressed by more than the allowed {max_regression}%",
+            "Error".red().bold(),
+        );
+        Ok(127)
+    } else {
+        Ok(0)
+    }
+}
+
+/// Write the flamegraph of the instructions executed by the binary into the
+/// archive directory as `flamegraph-<binary>.svg`.
+///
+/// Failures are reported as a warning, since the profile is still available.
+fn write_flamegraph(callgrind: &valgrind::callgrind::Callgrind, binary: &str) {
+    let name = Path::new(binary)
+        .file_name()
+        .map_or_else(|| "unknown".into(), OsStr::to_string_lossy);
+    let result = options::archive_directory()
+        .ok_or_else(|| io::Error::other("the target directory is unknown"))
+        .and_then(|directory| {
+            let file = directory.join(format!("flamegraph-{name}.svg"));
+            let stacks = callgrind.folded("Ir");
+            let title = format!("Instructions of {}", callgrind.command);
+            output::flamegraph::write(&file, &title, &stacks, "instructions")?;
+            Ok(file)
+        });
+    match result {
+        Ok(file) => output::display_flamegraph_written(&file),
+        Err(e) => eprintln!(
+            "{}: could not write the flamegraph: {e}",
+            "warning".yellow().bold()
+        ),
+    }
+}
@@ -0,0 +1,111 @@
+//! Support for recorded instruction counts of benchmarks.
+//!
+//! Wall-clock time is too noisy to detect performance regressions on shared CI
+//! runners, but the number of instructions executed under callgrind is nearly
+//! deterministic. Therefore the instruction count of every binary can be
+//! recorded in a results file. Later runs fail, if the instruction count of a
+//! binary grew by more than the allowed percentage.
+//!
+//! Binaries are identified by their file name without the hash suffix cargo
+//! appends to test and benchmark binaries (e.g. `parsing-0123456789abcdef`),
+//! since that changes with the toolchain and the build configuration.
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// The comment at the start of every results file.
+const HEADER: &str = "\
+# Instruction counts recorded by `cargo valgrind --tool=callgrind`, one binary per line.
+# Regenerate this file by running with `--results <FILE> --write-results`.
+";
+
+/// The length of the hash suffix of cargo, e.g. `0123456789abcdef`.
+const HASH_LENGTH: usize = 16;
+
+/// The recorded instruction counts of all binaries.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Results {
+    /// The instruction count by the name of the binary.
+    instructions: BTreeMap<String, u64>,
+}
+impl Results {
+    /// Load the results from the given file.
+    ///
+    /// Empty lines and lines starting with `#` are ignored.
+    ///
+    /// # Errors
+    /// Any I/O error during reading the file or a malformed line is returned.
+    pub fn load(file: &Path) -> io::Result<Self> {
+        let contents = fs::read_to_string(file)?;
+        let instructions = contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| {
+                line.rsplit_once(' ')
+                    .and_then(|(name, count)| Some((name.trim().to_string(), count.parse().ok()?)))
+                    .ok_or_else(|| {
+                        io::Error::new(io::ErrorKind::InvalidData, format!("invalid line `{line}`"))
+                    })
+            })
+            .collect::<io::Result<_>>()?;
+        Ok(Self { instructions })
+    }
+
+    /// Write the results to the given file.
+    ///
+    /// # Errors
+    /// Any I/O error during writing the file is returned.
+    pub fn write(&self, file: &Path) -> io::Result<()> {
+        let mut contents = String::from(HEADER);
+        for (name, instructions) in &self.instructions {
+            writeln!(contents, "{name} {instructions}").expect("writing to a string never fails");
+        }
+        fs::write(file, contents)
+    }
+
+    /// The number of binaries with a recorded instruction count.
+    pub fn len(&self) -> usize {
+        self.instructions.len()
+    }
+
+    /// Query the recorded instruction count of the binary with the given name.
+    pub fn get(&self, name: &str) -> Option<u64> {
+        self.instructions.get(name).copied()
+    }
+
+    /// Record the instruction count of the binary with the given name.
+    pub fn insert(&mut self, name: String, instructions: u64) {
+        self.instructions.insert(name, instructions);
+    }
+}
+
+/// Compute the name identifying a binary inside of the results file.
+///
+/// This is the file name of the binary without the hash suffix of cargo.
+pub fn name(binary: &str) -> String {
+    let name = Path::new(binary)
+        .file_name()
+        .map_or_else(|| binary.into(), |name| name.to_string_lossy());
+    name.rsplit_once('-')
+        .filter(|(_, hash)| {
+            hash.len() == HASH_LENGTH && hash.chars().all(|c| c.is_ascii_hexdigit())
+        })
+        .map_or_else(|| name.to_string(), |(name, _)| name.to_string())
+}
+
+/// Compute the change of the instruction count relative to the recorded one in
+/// percent (positive values are regressions).
+#[allow(clippy::cast_precision_loss)] // a percentage does not need to be exact
+pub fn change(recorded: u64, instructions: u64) -> f64 {
+    if recorded == 0 {
+        return if instructions == 0 {
+            0.0
+        } else {
+            f64::INFINITY
+        };
+    }
+    (instructions as f64 - recorded as f64) * 100.0 / recorded as f64
+}
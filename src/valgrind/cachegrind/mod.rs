@@ -0,0 +1,149 @@
+//! The output of the cache profiler cachegrind (`cachegrind.out.<pid>`).
+//!
+//! The format is described in the [cachegrind manual][manual]. It consists of a
+//! header (e.g. the recorded events) and cost lines, which are attributed to
+//! the most recently specified source file and function.
+//!
+//! [manual]: https://valgrind.org/docs/manual/cg-manual.html
+
+#[cfg(test)]
+mod tests;
+
+use super::xml::Frame;
+use super::{add_costs, parse_costs, symbol};
+use std::collections::HashMap;
+
+/// A parsed cachegrind output file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Cachegrind {
+    /// The profiled command.
+    pub command: String,
+    /// The names of the recorded events, e.g. `Ir` for the executed
+    /// instructions or `D1mr` for the read misses of the first level data cache.
+    pub events: Vec<String>,
+    /// The total cost of the whole run per event.
+    pub totals: Vec<u64>,
+    /// The cost of every source line, that was executed.
+    pub lines: Vec<Line>,
+}
+impl Cachegrind {
+    /// Summarize the cost of the recorded events as cache misses.
+    ///
+    /// Events, which were not recorded, are counted as zero.
+    pub fn misses(&self, cost: &[u64]) -> Misses {
+        let sum = |events: &[&str]| {
+            self.events
+                .iter()
+                .zip(cost)
+                .filter(|(event, _)| events.contains(&event.as_str()))
+                .map(|(_, cost)| cost)
+                .sum()
+        };
+        Misses {
+            instructions: sum(&["Ir"]),
+            i1: sum(&["I1mr"]),
+            d1: sum(&["D1mr", "D1mw"]),
+            ll: sum(&["ILmr", "DLmr", "DLmw"]),
+        }
+    }
+
+    /// Query, whether the cache simulation was enabled, i.e. whether cache
+    /// misses were recorded.
+    pub fn has_cache_events(&self) -> bool {
+        self.events.iter().any(|event| event == "I1mr")
+    }
+}
+
+/// The cost of a single source line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Line {
+    /// The location of the line.
+    ///
+    /// Cachegrind does not record instruction pointers, so those are zero.
+    pub frame: Frame,
+    /// The cost of the line per event.
+    pub cost: Vec<u64>,
+}
+
+/// The executed instructions and the cache misses of a part of the program.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Misses {
+    /// The number of executed instructions.
+    pub instructions: u64,
+    /// The number of misses of the first level instruction cache.
+    pub i1: u64,
+    /// The number of read and write misses of the first level data cache.
+    pub d1: u64,
+    /// The number of instruction, read and write misses of the last level
+    /// cache.
+    pub ll: u64,
+}
+impl std::ops::AddAssign for Misses {
+    fn add_assign(&mut self, other: Self) {
+        self.instructions += other.instructions;
+        self.i1 += other.i1;
+        self.d1 += other.d1;
+        self.ll += other.ll;
+    }
+}
+
+/// Parse the contents of a cachegrind output file.
+///
+/// Multiple cost lines of the same function and source line are merged. If the
+/// file does not state the total cost, it is computed from all lines.
+///
+/// # Errors
+/// A description of the problem is returned, if the output is malformed.
+pub fn parse(output: &str) -> Result<Cachegrind, String> {
+    let mut cachegrind = Cachegrind::default();
+    let mut indices: HashMap<Frame, usize> = HashMap::new();
+    let mut file = None;
+    let mut function = None;
+    for line in output.lines() {
+        if line.starts_with(|c: char| c.is_ascii_digit()) {
+            let mut columns = line.split_whitespace();
+            let number = columns.next().unwrap_or_default();
+            let number = number
+                .parse()
+                .map_err(|_| format!("invalid line number `{number}`"))?;
+            let cost = parse_costs(columns)?;
+            let frame = Frame {
+                instruction_pointer: 0,
+                object: None,
+                directory: None,
+                function: function.clone(),
+                file: file.clone(),
+                line: Some(number),
+            };
+            let index = *indices
+                .entry(frame.clone())
+                .or_insert(cachegrind.lines.len());
+            if index == cachegrind.lines.len() {
+                cachegrind.lines.push(Line { frame, cost });
+            } else {
+                add_costs(&mut cachegrind.lines[index].cost, &cost);
+            }
+        } else if let Some(name) = line.strip_prefix("fl=") {
+            file = Some(name.to_string()).filter(|name| name != "???");
+        } else if let Some(name) = line.strip_prefix("fn=") {
+            function = Some(name)
+                .filter(|name| *name != "???")
+                .map(symbol::demangle);
+        } else if let Some(command) = line.strip_prefix("cmd: ") {
+            cachegrind.command = command.to_string();
+        } else if let Some(events) = line.strip_prefix("events: ") {
+            cachegrind.events = events.split_whitespace().map(String::from).collect();
+        } else if let Some(summary) = line.strip_prefix("summary: ") {
+            cachegrind.totals = parse_costs(summary.split_whitespace())?;
+        }
+    }
+
+    if cachegrind.totals.is_empty() {
+        let mut totals = Vec::new();
+        for line in &cachegrind.lines {
+            add_costs(&mut totals, &line.cost);
+        }
+        cachegrind.totals = totals;
+    }
+    Ok(cachegrind)
+}
@@ -0,0 +1,47 @@
+use super::{parse, Misses};
+use std::fs;
+
+#[test]
+fn lines_are_parsed_and_merged() {
+    let cachegrind = fs::read_to_string("src/valgrind/cachegrind/cachegrind.out")
+        .expect("Could not open test file");
+    let cachegrind = parse(&cachegrind).expect("Could not read test file");
+    assert_eq!(cachegrind.command, "/project/target/release/matrix");
+    assert!(cachegrind.has_cache_events());
+    assert_eq!(cachegrind.lines.len(), 5);
+
+    let first = &cachegrind.lines[0];
+    assert_eq!(first.frame.function.as_deref(), Some("matrix::multiply"));
+    assert_eq!(first.frame.file.as_deref(), Some("/project/src/main.rs"));
+    assert_eq!(first.frame.line, Some(10));
+    assert_eq!(first.cost, [1500, 2, 2, 500, 120, 10, 100, 5, 1]);
+
+    let unknown = &cachegrind.lines[4];
+    assert_eq!(
+        (&unknown.frame.function, &unknown.frame.file),
+        (&None, &None)
+    );
+
+    assert_eq!(
+        cachegrind.misses(&cachegrind.totals),
+        Misses {
+            instructions: 6000,
+            i1: 12,
+            d1: 977,
+            ll: 76,
+        }
+    );
+}
+
+#[test]
+fn missing_cache_events_are_zero() {
+    let cachegrind = parse("events: Ir\nfl=main.rs\nfn=main\n1 10\n2 5\n").expect("valid output");
+    assert!(!cachegrind.has_cache_events());
+    assert_eq!(
+        cachegrind.misses(&cachegrind.totals),
+        Misses {
+            instructions: 15,
+            ..Misses::default()
+        }
+    );
+}
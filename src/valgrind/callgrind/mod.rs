@@ -0,0 +1,368 @@
+//! The output of the call-graph profiler callgrind (`callgrind.out.<pid>`).
+//!
+//! The format is described in the [callgrind manual][manual]. It consists of a
+//! header (e.g. the recorded events) and cost lines, which are attributed to
+//! the most recently specified function. Calls to other functions are listed
+//! together with the inclusive cost of the call. Names of files, objects and
+//! functions may be compressed, i.e. be given only once as `(<id>) <name>` and
+//! referenced afterwards by `(<id>)` only. A file may consist of multiple parts
+//! (e.g. with `--dump-every-bb`), whose costs are added up.
+//!
+//! [manual]: https://valgrind.org/docs/manual/cl-format.html
+
+#[cfg(test)]
+mod tests;
+
+use super::{add_costs, parse_costs, symbol};
+use std::collections::{BTreeMap, HashMap};
+
+/// The maximum depth of the stacks created by [`Callgrind::folded()`].
+const MAX_DEPTH: usize = 256;
+/// The fraction of the total cost, below which calls are not expanded into
+/// their own stacks by [`Callgrind::folded()`], but attributed to the caller.
+const MIN_SHARE: f64 = 1e-4;
+
+/// A parsed callgrind output file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Callgrind {
+    /// The profiled command.
+    pub command: String,
+    /// The names of the recorded events, e.g. `Ir` for the executed
+    /// instructions.
+    pub events: Vec<String>,
+    /// The total cost of the whole run (i.e. of all parts) per event.
+    pub totals: Vec<u64>,
+    /// All functions, that were executed.
+    pub functions: Vec<Function>,
+}
+impl Callgrind {
+    /// The total cost of the given event (e.g. `Ir`), if it was recorded.
+    pub fn total(&self, event: &str) -> Option<u64> {
+        let index = self.events.iter().position(|name| name == event)?;
+        Some(self.totals.get(index).copied().unwrap_or_default())
+    }
+
+    /// Convert the call graph into folded stacks, i.e. the cost of the given
+    /// event per unique stack of function names (outermost function first).
+    ///
+    /// Callgrind only records the cost of the calls between two functions, not
+    /// of complete stacks. Therefore the cost of a function is distributed onto
+    /// its stacks proportionally to the inclusive cost of the calls, that lead
+    /// to it. Recursive calls are attributed to the outermost occurrence of the
+    /// function and tiny calls (see [`MIN_SHARE`]) to their caller, so that the
+    /// number of stacks stays manageable.
+    pub fn folded(&self, event: &str) -> Vec<(Vec<&str>, u64)> {
+        let Some(event) = self.events.iter().position(|name| name == event) else {
+            return Vec::new();
+        };
+        let cost = |cost: &[u64]| cost.get(event).copied().unwrap_or_default();
+        let mut called = vec![false; self.functions.len()];
+        for (index, function) in self.functions.iter().enumerate() {
+            for call in function.calls.iter().filter(|call| call.callee != index) {
+                called[call.callee] = true;
+            }
+        }
+        let inclusive: Vec<u64> = self
+            .functions
+            .iter()
+            .enumerate()
+            .map(|(index, function)| {
+                cost(&function.self_cost)
+                    + function
+                        .calls
+                        .iter()
+                        .filter(|call| call.callee != index)
+                        .map(|call| cost(&call.cost))
+                        .sum::<u64>()
+            })
+            .collect();
+
+        #[allow(clippy::cast_precision_loss)] // costs are distributed approximately
+        let min_share = self.total(&self.events[event]).unwrap_or_default() as f64 * MIN_SHARE;
+        let mut folder = Folder {
+            callgrind: self,
+            cost: &cost,
+            inclusive: &inclusive,
+            min_share,
+            stack: Vec::new(),
+            stacks: BTreeMap::new(),
+        };
+        for (root, _) in called.iter().enumerate().filter(|(_, called)| !**called) {
+            #[allow(clippy::cast_precision_loss)] // costs are distributed approximately
+            folder.fold(root, inclusive[root] as f64);
+        }
+
+        folder
+            .stacks
+            .into_iter()
+            .filter_map(|(stack, cost)| {
+                #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+                let cost = cost.round() as u64;
+                let stack = stack
+                    .iter()
+                    .map(|&index| self.functions[index].name.as_str())
+                    .collect();
+                (cost > 0).then_some((stack, cost))
+            })
+            .collect()
+    }
+}
+
+/// The state of the conversion of the call graph into folded stacks.
+struct Folder<'a> {
+    /// The call graph to convert.
+    callgrind: &'a Callgrind,
+    /// Extract the cost of the selected event.
+    cost: &'a dyn Fn(&[u64]) -> u64,
+    /// The inclusive cost of every function.
+    inclusive: &'a [u64],
+    /// The cost, below which calls are attributed to their caller.
+    min_share: f64,
+    /// The indices of the functions of the current stack.
+    stack: Vec<usize>,
+    /// The cost of every stack found so far.
+    stacks: BTreeMap<Vec<usize>, f64>,
+}
+impl Folder<'_> {
+    /// Distribute the `share` of the inclusive cost of the function onto the
+    /// function itself and its callees on top of the current stack.
+    #[allow(clippy::cast_precision_loss)] // costs are distributed approximately
+    fn fold(&mut self, function: usize, share: f64) {
+        if self.inclusive[function] == 0 {
+            return;
+        }
+        self.stack.push(function);
+        let scale = share / self.inclusive[function] as f64;
+        let callgrind = self.callgrind;
+        let mut own = (self.cost)(&callgrind.functions[function].self_cost) as f64 * scale;
+        for call in &callgrind.functions[function].calls {
+            if call.callee == function {
+                continue; // the cost is part of the inclusive cost already
+            }
+            let call_share = (self.cost)(&call.cost) as f64 * scale;
+            if call_share < self.min_share
+                || self.stack.len() >= MAX_DEPTH
+                || self.stack.contains(&call.callee)
+            {
+                own += call_share;
+            } else {
+                self.fold(call.callee, call_share);
+            }
+        }
+        *self.stacks.entry(self.stack.clone()).or_default() += own;
+        self.stack.pop();
+    }
+}
+
+/// A function with the cost of its own instructions and its calls.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Function {
+    /// The demangled name of the function.
+    pub name: String,
+    /// The object file containing the function.
+    pub object: Option<String>,
+    /// The source file of the function.
+    pub file: Option<String>,
+    /// The cost of the function itself (exclusive cost) per event.
+    pub self_cost: Vec<u64>,
+    /// The calls to other functions.
+    pub calls: Vec<Call>,
+}
+
+/// A call from one function to another.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Call {
+    /// The index of the called function inside of [`Callgrind::functions`].
+    pub callee: usize,
+    /// The number of calls.
+    pub count: u64,
+    /// The cost of all calls including the callees (inclusive cost) per event.
+    pub cost: Vec<u64>,
+}
+
+/// The state of the parser, that is changed by the specification lines.
+#[derive(Debug, Default)]
+struct Parser {
+    /// The result, that is built up.
+    callgrind: Callgrind,
+    /// The number of position columns in front of the costs of a cost line.
+    positions: usize,
+    /// The decompressed names of object files by their id.
+    objects: HashMap<String, String>,
+    /// The decompressed names of source files by their id.
+    files: HashMap<String, String>,
+    /// The decompressed names of functions by their id.
+    names: HashMap<String, String>,
+    /// The index of a function by its object file, source file and (mangled)
+    /// name, which together identify a function like in callgrind itself.
+    functions: HashMap<(Option<String>, Option<String>, String), usize>,
+    /// The total cost of the current part, if it is stated.
+    part_totals: Option<Vec<u64>>,
+    /// The current object file.
+    object: Option<String>,
+    /// The current source file.
+    file: Option<String>,
+    /// The object file of the next called function.
+    callee_object: Option<String>,
+    /// The source file of the next called function.
+    callee_file: Option<String>,
+    /// The index of the current function.
+    function: Option<usize>,
+    /// The called function and the number of calls, if the next cost line is
+    /// the inclusive cost of a call.
+    call: Option<(usize, u64)>,
+}
+impl Parser {
+    /// Resolve a possibly compressed name using the given table.
+    fn decompress(table: &mut HashMap<String, String>, value: &str) -> String {
+        let Some(rest) = value.strip_prefix('(') else {
+            return value.to_string();
+        };
+        let Some((id, name)) = rest.split_once(')') else {
+            return value.to_string();
+        };
+        let name = name.trim_start();
+        if name.is_empty() {
+            table.get(id).cloned().unwrap_or_default()
+        } else {
+            table.insert(id.to_string(), name.to_string());
+            name.to_string()
+        }
+    }
+
+    /// Query the index of the function with the given (compressed) name inside
+    /// of the object and source file, which is added to the list of functions if
+    /// it is unknown so far.
+    fn function_index(
+        &mut self,
+        name: &str,
+        object: Option<String>,
+        file: Option<String>,
+    ) -> usize {
+        let name = Self::decompress(&mut self.names, name);
+        let key = (object, file, name);
+        if let Some(&index) = self.functions.get(&key) {
+            return index;
+        }
+        let index = self.callgrind.functions.len();
+        self.callgrind.functions.push(Function {
+            name: symbol::demangle(&key.2),
+            object: key.0.clone(),
+            file: key.1.clone(),
+            ..Function::default()
+        });
+        self.functions.insert(key, index);
+        index
+    }
+
+    /// Add the total cost of the current part to the total cost of the run.
+    fn finish_part(&mut self) {
+        if let Some(totals) = self.part_totals.take() {
+            add_costs(&mut self.callgrind.totals, &totals);
+        }
+    }
+
+    /// Handle a single line of the output file.
+    fn line(&mut self, line: &str) -> Result<(), String> {
+        if line.starts_with(|c: char| c.is_ascii_digit() || matches!(c, '+' | '-' | '*')) {
+            return self.cost_line(line);
+        }
+        // specification lines use `=`, header lines use `:`
+        let Some(separator) = line.find(['=', ':']) else {
+            return Ok(()); // empty lines and unknown lines
+        };
+        let (key, value) = (&line[..separator], line[separator + 1..].trim());
+        match key {
+            "cmd" => self.callgrind.command = value.to_string(),
+            "positions" => self.positions = value.split_whitespace().count(),
+            "events" => {
+                self.callgrind.events = value.split_whitespace().map(String::from).collect();
+            }
+            "part" => self.finish_part(),
+            // both state the total cost of the current part
+            "summary" | "totals" => self.part_totals = Some(parse_costs(value.split_whitespace())?),
+            "ob" => self.object = Some(Self::decompress(&mut self.objects, value)),
+            "fl" => self.file = Some(Self::decompress(&mut self.files, value)),
+            "cob" => self.callee_object = Some(Self::decompress(&mut self.objects, value)),
+            "cfi" | "cfl" => self.callee_file = Some(Self::decompress(&mut self.files, value)),
+            "fi" | "fe" => {
+                Self::decompress(&mut self.files, value);
+            }
+            "fn" => {
+                let index = self.function_index(value, self.object.clone(), self.file.clone());
+                self.function = Some(index);
+                self.callee_object = None;
+                self.callee_file = None;
+            }
+            "cfn" => {
+                let object = self.callee_object.clone().or_else(|| self.object.clone());
+                let file = self.callee_file.clone().or_else(|| self.file.clone());
+                let callee = self.function_index(value, object, file);
+                self.call = Some((callee, 0));
+                self.callee_object = None;
+                self.callee_file = None;
+            }
+            "calls" => {
+                let count = value.split_whitespace().next().unwrap_or_default();
+                let count = count
+                    .parse()
+                    .map_err(|_| format!("invalid call count `{count}`"))?;
+                if let Some((_, calls)) = &mut self.call {
+                    *calls = count;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Handle a cost line, which is either the exclusive cost of the current
+    /// function or the inclusive cost of a call.
+    fn cost_line(&mut self, line: &str) -> Result<(), String> {
+        let function = self
+            .function
+            .ok_or_else(|| format!("cost line `{line}` outside of a function"))?;
+        let costs = parse_costs(line.split_whitespace().skip(self.positions.max(1)))?;
+        let function = &mut self.callgrind.functions[function];
+        match self.call.take() {
+            Some((callee, count)) => {
+                match function.calls.iter_mut().find(|call| call.callee == callee) {
+                    Some(call) => {
+                        call.count += count;
+                        add_costs(&mut call.cost, &costs);
+                    }
+                    None => function.calls.push(Call {
+                        callee,
+                        count,
+                        cost: costs,
+                    }),
+                }
+            }
+            None => add_costs(&mut function.self_cost, &costs),
+        }
+        Ok(())
+    }
+}
+
+/// Parse the contents of a callgrind output file.
+///
+/// The costs of all parts of the file are added up. If the file does not state
+/// the total cost, it is computed from the cost of all functions.
+///
+/// # Errors
+/// A description of the problem is returned, if the output is malformed.
+pub fn parse(output: &str) -> Result<Callgrind, String> {
+    let mut parser = Parser::default();
+    for line in output.lines() {
+        parser.line(line)?;
+    }
+    parser.finish_part();
+
+    let mut callgrind = parser.callgrind;
+    if callgrind.totals.is_empty() {
+        for function in &callgrind.functions {
+            add_costs(&mut callgrind.totals, &function.self_cost);
+        }
+    }
+    Ok(callgrind)
+}
@@ -0,0 +1,94 @@
+use super::parse;
+use std::fs;
+
+#[test]
+fn totals_and_functions_are_parsed() {
+    let callgrind = fs::read_to_string("src/valgrind/callgrind/callgrind.out")
+        .expect("Could not open test file");
+    let callgrind = parse(&callgrind).expect("Could not read test file");
+    assert_eq!(
+        callgrind.command,
+        "/project/target/release/deps/parsing-0123456789abcdef --bench"
+    );
+    assert_eq!(callgrind.events, ["Ir", "Dr", "Dw"]);
+    assert_eq!(callgrind.total("Ir"), Some(10000));
+    assert_eq!(callgrind.total("Dw"), Some(1000));
+    assert_eq!(callgrind.total("I1mr"), None);
+
+    let names: Vec<_> = callgrind
+        .functions
+        .iter()
+        .map(|function| function.name.as_str())
+        .collect();
+    assert_eq!(
+        names,
+        ["main", "parsing::bench", "memcpy", "parsing::parse_value"]
+    );
+
+    let bench = &callgrind.functions[1];
+    assert_eq!(bench.file.as_deref(), Some("/project/benches/parsing.rs"));
+    assert_eq!(bench.self_cost, [150, 30, 15]);
+    assert_eq!(bench.calls.len(), 2);
+    assert_eq!(bench.calls[0].callee, 2);
+    assert_eq!(bench.calls[0].count, 20);
+    assert_eq!(bench.calls[0].cost, [750, 350, 175]);
+
+    let memcpy = &callgrind.functions[2];
+    assert_eq!(memcpy.object.as_deref(), Some("/usr/lib/libc.so.6"));
+    assert_eq!(memcpy.self_cost, [1750, 550, 275]);
+}
+
+#[test]
+fn missing_totals_are_computed() {
+    let callgrind = parse("events: Ir\nfn=a\n1 10\ncfn=b\ncalls=1 0\n1 5\nfn=b\n2 5\n")
+        .expect("valid callgrind output");
+    assert_eq!(callgrind.total("Ir"), Some(15));
+}
+
+#[test]
+fn costs_of_all_parts_are_added_up() {
+    let callgrind = parse(
+        "part: 1\nevents: Ir\nsummary: 15\nfn=a\n1 10\nfn=b\n2 5\ntotals: 15\n\
+         part: 2\nevents: Ir\nsummary: 7\nfn=a\n1 7\ntotals: 7\n",
+    )
+    .expect("valid callgrind output");
+    assert_eq!(callgrind.total("Ir"), Some(22));
+    assert_eq!(callgrind.functions.len(), 2);
+    assert_eq!(callgrind.functions[0].self_cost, [17]);
+}
+
+#[test]
+fn functions_of_different_objects_are_kept_apart() {
+    let callgrind = parse(
+        "events: Ir\nob=(1) /lib/a.so\nfl=(1) a.c\nfn=(1) init\n1 10\n\
+         ob=(2) /lib/b.so\nfl=(2) b.c\nfn=(1)\n1 5\n",
+    )
+    .expect("valid callgrind output");
+    assert_eq!(callgrind.functions.len(), 2);
+    assert_eq!(callgrind.functions[0].name, callgrind.functions[1].name);
+    assert_eq!(callgrind.functions[0].object.as_deref(), Some("/lib/a.so"));
+    assert_eq!(callgrind.functions[1].self_cost, [5]);
+}
+
+#[test]
+fn call_graph_is_folded_into_stacks() {
+    let callgrind = fs::read_to_string("src/valgrind/callgrind/callgrind.out")
+        .expect("Could not open test file");
+    let callgrind = parse(&callgrind).expect("Could not read test file");
+    let folded = callgrind.folded("Ir");
+    assert_eq!(
+        folded,
+        [
+            (vec!["main"], 100),
+            (vec!["main", "parsing::bench"], 150),
+            (vec!["main", "parsing::bench", "memcpy"], 750),
+            (vec!["main", "parsing::bench", "parsing::parse_value"], 8000),
+            (
+                vec!["main", "parsing::bench", "parsing::parse_value", "memcpy"],
+                1000
+            ),
+        ]
+    );
+    assert_eq!(folded.iter().map(|(_, cost)| cost).sum::<u64>(), 10000);
+    assert!(callgrind.folded("unknown").is_empty());
+}
@@ -0,0 +1,237 @@
+//! The channel, over which valgrind sends its XML output to `cargo valgrind`.
+//!
+//! By default, valgrind writes the XML into a pipe, whose write end it inherits
+//! (`--xml-fd`). This neither needs any network access nor can other processes
+//! interfere with it. If child processes are traced, every process sends its
+//! own XML document, which would be interleaved in a single pipe. Therefore a
+//! TCP listener on localhost is used instead (`--xml-socket`), to which every
+//! process connects separately. This is also the fallback on platforms without
+//! pipes.
+
+#[cfg(test)]
+mod tests;
+
+use super::Error;
+use std::io::{self, Read};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// The interval, in which connections of late child processes are polled for
+/// after the program under test exited.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// The receiving end of the XML output of valgrind.
+#[derive(Debug)]
+pub enum Channel {
+    /// A pipe, whose write end is inherited by valgrind.
+    ///
+    /// The write end is closed in this process, once the receiving starts, so
+    /// that the end of the output is detected after valgrind exited.
+    #[cfg(unix)]
+    Pipe {
+        /// The read end of the pipe.
+        reader: std::fs::File,
+        /// The write end of the pipe, which is passed to valgrind.
+        writer: std::os::fd::OwnedFd,
+    },
+    /// A TCP listener on localhost, port selected by the OS.
+    Socket(TcpListener),
+}
+
+impl Channel {
+    /// Open a new channel.
+    ///
+    /// If `all` processes should be able to send their output (i.e. if child
+    /// processes are traced), a socket is opened, otherwise a pipe is preferred.
+    ///
+    /// # Errors
+    /// This function returns [`Error::XmlChannel`], if neither a pipe nor a
+    /// socket could be opened.
+    pub fn open(all: bool) -> Result<Self, Error> {
+        #[cfg(unix)]
+        if !all {
+            if let Ok(pipe) = Self::pipe() {
+                return Ok(pipe);
+            }
+        }
+        let address: SocketAddr = ([127, 0, 0, 1], 0).into();
+        let listener = TcpListener::bind(address).map_err(|_| Error::XmlChannel)?;
+        Ok(Self::Socket(listener))
+    }
+
+    /// Open a pipe, whose write end is inherited by child processes, while the
+    /// read end is not.
+    #[cfg(unix)]
+    fn pipe() -> std::io::Result<Self> {
+        use rustix::io::{fcntl_setfd, FdFlags};
+
+        let (reader, writer) = rustix::pipe::pipe()?;
+        fcntl_setfd(&reader, FdFlags::CLOEXEC)?;
+        fcntl_setfd(&writer, FdFlags::empty())?;
+        Ok(Self::Pipe {
+            reader: reader.into(),
+            writer,
+        })
+    }
+
+    /// The command line option, which tells valgrind to use this channel.
+    ///
+    /// # Errors
+    /// This function returns [`Error::XmlChannel`], if the address of the
+    /// socket is not available.
+    pub fn option(&self) -> Result<String, Error> {
+        match self {
+            #[cfg(unix)]
+            Self::Pipe { writer, .. } => {
+                use std::os::fd::AsRawFd;
+                Ok(format!("--xml-fd={}", writer.as_raw_fd()))
+            }
+            Self::Socket(listener) => {
+                let address = listener.local_addr().map_err(|_| Error::XmlChannel)?;
+                Ok(format!("--xml-socket={}:{}", address.ip(), address.port()))
+            }
+        }
+    }
+
+    /// Start receiving the XML output in a separate thread.
+    ///
+    /// Every stream of XML output is passed to `handle` together with its index
+    /// (in the order of the connections) while it is received. This has to be
+    /// called after valgrind was started, since the write end of a pipe is
+    /// closed in this process. On a socket, only the first connection is
+    /// accepted, unless `all` processes send their output.
+    pub fn receive<T, F>(self, all: bool, handle: F) -> Receiver<T>
+    where
+        T: Send + 'static,
+        F: Fn(usize, &mut dyn Read) -> T + Send + Sync + 'static,
+    {
+        let finished = Arc::new(AtomicBool::new(false));
+        match self {
+            #[cfg(unix)]
+            Self::Pipe { mut reader, writer } => {
+                drop(writer);
+                let thread = std::thread::spawn(move || Ok(vec![handle(0, &mut reader)]));
+                Receiver {
+                    thread,
+                    wake_up: None,
+                    finished,
+                }
+            }
+            Self::Socket(listener) => {
+                // the `accept()` call blocks until valgrind connects to the
+                // socket. This is normally fine, but if we consider errors, e.g.
+                // wrong command line flags, valgrind won't connect at all, so
+                // the pending `accept()` is woken up after valgrind exited.
+                let wake_up = listener.local_addr().ok();
+                let thread = {
+                    let finished = Arc::clone(&finished);
+                    std::thread::spawn(move || accept(&listener, all, &finished, handle))
+                };
+                Receiver {
+                    thread,
+                    wake_up,
+                    finished,
+                }
+            }
+        }
+    }
+}
+
+/// A running reception of the XML output of valgrind (see [`Channel::receive()`]).
+#[derive(Debug)]
+pub struct Receiver<T> {
+    /// The thread receiving the output.
+    thread: JoinHandle<Result<Vec<T>, Error>>,
+    /// The address of the socket, to which a wake-up connection is made.
+    wake_up: Option<SocketAddr>,
+    /// Whether valgrind exited already.
+    finished: Arc<AtomicBool>,
+}
+
+impl<T> Receiver<T> {
+    /// Finish the reception after valgrind exited.
+    ///
+    /// The results of handling every stream are returned in the order of the
+    /// connections. This includes the (empty) wake-up connection of a socket.
+    ///
+    /// # Errors
+    /// This function returns [`Error::XmlChannel`], if the connections could
+    /// not be accepted.
+    pub fn finish(self) -> Result<Vec<T>, Error> {
+        self.finished.store(true, Ordering::SeqCst);
+        if let Some(address) = self.wake_up {
+            TcpStream::connect(address).ok();
+        }
+        self.thread.join().expect("Reader-thread panicked")
+    }
+}
+
+/// Accept the connections of valgrind on the listener.
+///
+/// Only the first connection is accepted, unless `all` processes send their
+/// output: then every connection is accepted until the program under test is
+/// `finished`, which is signaled by an additional (empty) connection, and all
+/// connected processes closed their connections, since they might start further
+/// child processes until then. The connections are handled concurrently, since
+/// the processes run concurrently as well.
+fn accept<T, F>(
+    listener: &TcpListener,
+    all: bool,
+    finished: &AtomicBool,
+    handle: F,
+) -> Result<Vec<T>, Error>
+where
+    T: Send + 'static,
+    F: Fn(usize, &mut dyn Read) -> T + Send + Sync + 'static,
+{
+    let handle = Arc::new(handle);
+    let mut readers: Vec<JoinHandle<T>> = Vec::new();
+    let read = |readers: &mut Vec<_>, mut stream: TcpStream| {
+        let handle = Arc::clone(&handle);
+        let index = readers.len();
+        readers.push(std::thread::spawn(move || handle(index, &mut stream)));
+    };
+
+    loop {
+        let (stream, _) = listener.accept().map_err(|_| Error::XmlChannel)?;
+        read(&mut readers, stream);
+        if !all {
+            break;
+        }
+        if finished.load(Ordering::SeqCst) {
+            // the program under test exited already, but its child processes
+            // might still run (e.g. daemons) and start further processes.
+            // Therefore connections are accepted without blocking, until all
+            // connected processes are done.
+            listener
+                .set_nonblocking(true)
+                .map_err(|_| Error::XmlChannel)?;
+            loop {
+                match listener.accept() {
+                    Ok((stream, _)) => {
+                        stream
+                            .set_nonblocking(false)
+                            .map_err(|_| Error::XmlChannel)?;
+                        read(&mut readers, stream);
+                    }
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                        if readers.iter().all(JoinHandle::is_finished) {
+                            break;
+                        }
+                        std::thread::sleep(POLL_INTERVAL);
+                    }
+                    Err(_) => return Err(Error::XmlChannel),
+                }
+            }
+            break;
+        }
+    }
+
+    Ok(readers
+        .into_iter()
+        .map(|reader| reader.join().expect("Reader-thread panicked"))
+        .collect())
+}
@@ -0,0 +1,121 @@
+use super::Channel;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::process::Command;
+
+/// Read a whole stream of output.
+fn read(_index: usize, stream: &mut dyn Read) -> Vec<u8> {
+    let mut output = Vec::new();
+    stream
+        .read_to_end(&mut output)
+        .expect("Could not read output");
+    output
+}
+
+#[test]
+#[cfg(unix)]
+fn pipe_is_preferred_for_a_single_process() {
+    let channel = Channel::open(false).expect("Could not open channel");
+    assert!(matches!(channel, Channel::Pipe { .. }));
+    let option = channel.option().expect("Could not get option");
+    assert!(option.starts_with("--xml-fd="));
+}
+
+#[test]
+fn socket_is_used_for_traced_child_processes() {
+    let channel = Channel::open(true).expect("Could not open channel");
+    assert!(matches!(channel, Channel::Socket(_)));
+    let option = channel.option().expect("Could not get option");
+    assert!(option.starts_with("--xml-socket=127.0.0.1:"));
+}
+
+#[test]
+#[cfg(unix)]
+fn write_end_of_pipe_is_inherited_by_child_processes() {
+    let channel = Channel::open(false).expect("Could not open channel");
+    let option = channel.option().expect("Could not get option");
+    let fd = option.trim_start_matches("--xml-fd=");
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(format!("printf '<valgrindoutput/>' >&{fd}"))
+        .spawn()
+        .expect("Could not start shell");
+    let receiver = channel.receive(false, read);
+    assert!(child.wait().expect("Shell did not run").success());
+
+    let outputs = receiver.finish().expect("Could not receive output");
+    assert_eq!(outputs, [b"<valgrindoutput/>"]);
+}
+
+#[test]
+#[cfg(unix)]
+fn nothing_is_received_if_the_pipe_is_not_used() {
+    let channel = Channel::open(false).expect("Could not open channel");
+    let mut child = Command::new("true")
+        .spawn()
+        .expect("Could not start `true`");
+    let receiver = channel.receive(false, read);
+    assert!(child.wait().expect("`true` did not run").success());
+
+    let outputs = receiver.finish().expect("Could not receive output");
+    assert_eq!(outputs, [b""]);
+}
+
+#[test]
+fn every_connection_is_received_on_the_socket() {
+    let channel = Channel::open(true).expect("Could not open channel");
+    let address = match &channel {
+        Channel::Socket(listener) => listener.local_addr().expect("Socket has no address"),
+        #[cfg(unix)]
+        Channel::Pipe { .. } => unreachable!("a socket is required for all processes"),
+    };
+    let receiver = channel.receive(true, read);
+    for output in ["<first/>", "<second/>"] {
+        let mut stream = TcpStream::connect(address).expect("Could not connect");
+        stream
+            .write_all(output.as_bytes())
+            .expect("Could not send output");
+    }
+
+    // the last connection is the wake-up connection
+    let outputs = receiver.finish().expect("Could not receive output");
+    assert_eq!(
+        outputs,
+        [b"<first/>".to_vec(), b"<second/>".to_vec(), Vec::new()]
+    );
+}
+
+#[test]
+fn late_connections_are_received_while_other_processes_run() {
+    let channel = Channel::open(true).expect("Could not open channel");
+    let address = match &channel {
+        Channel::Socket(listener) => listener.local_addr().expect("Socket has no address"),
+        #[cfg(unix)]
+        Channel::Pipe { .. } => unreachable!("a socket is required for all processes"),
+    };
+    let receiver = channel.receive(true, read);
+    let mut running = TcpStream::connect(address).expect("Could not connect");
+    running
+        .write_all(b"<running/>")
+        .expect("Could not send output");
+
+    // the program under test exited, but a child process is still running and
+    // starts another one
+    let finished = std::thread::spawn(move || receiver.finish());
+    std::thread::sleep(std::time::Duration::from_millis(100));
+    TcpStream::connect(address)
+        .and_then(|mut late| late.write_all(b"<late/>"))
+        .expect("Could not send output");
+    std::thread::sleep(std::time::Duration::from_millis(100));
+    drop(running);
+
+    let mut outputs = finished
+        .join()
+        .expect("Reader-thread panicked")
+        .expect("Could not receive output");
+    outputs.sort();
+    assert_eq!(
+        outputs,
+        [Vec::new(), b"<late/>".to_vec(), b"<running/>".to_vec()]
+    );
+}
@@ -0,0 +1,150 @@
+//! The output of the allocation profiler DHAT (`dhat.out.<pid>`).
+//!
+//! The output is a JSON document described in the [DHAT manual][manual] and in
+//! the viewer (`dh_view.js`) shipped with valgrind. Each program point is a
+//! stack trace, that allocated memory, together with statistics about all the
+//! blocks allocated by it.
+//!
+//! [manual]: https://valgrind.org/docs/manual/dh-manual.html
+
+#[cfg(test)]
+mod tests;
+
+use super::xml::Frame;
+use serde::Deserialize;
+
+/// A parsed DHAT output file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dhat {
+    /// The profiled command.
+    pub command: String,
+    /// Whether the accesses to the blocks were tracked, i.e. whether the read
+    /// and written bytes are available.
+    pub accesses_tracked: bool,
+    /// The lifetime (in instructions), below which a block is short-lived.
+    pub short_lived_threshold: u64,
+    /// All program points, that allocated memory.
+    pub program_points: Vec<ProgramPoint>,
+}
+
+/// A stack trace, that allocated memory, with the statistics of its blocks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProgramPoint {
+    /// The total number of bytes allocated.
+    pub total_bytes: u64,
+    /// The total number of blocks allocated.
+    pub total_blocks: u64,
+    /// The sum of the lifetimes of all blocks (in instructions).
+    pub total_lifetime: u64,
+    /// The number of bytes read from the blocks (if accesses were tracked).
+    pub read_bytes: Option<u64>,
+    /// The number of bytes written to the blocks (if accesses were tracked).
+    pub written_bytes: Option<u64>,
+    /// The stack trace of the allocation (innermost frame first).
+    pub frames: Vec<Frame>,
+}
+impl ProgramPoint {
+    /// The average lifetime of a block (in instructions).
+    pub const fn average_lifetime(&self) -> u64 {
+        match self.total_blocks {
+            0 => 0,
+            blocks => self.total_lifetime / blocks,
+        }
+    }
+}
+
+/// The raw structure of the DHAT output file.
+#[derive(Debug, Deserialize)]
+struct RawDhat {
+    /// The version of the file format.
+    #[serde(rename = "dhatFileVersion")]
+    version: u32,
+    /// The profiling mode (`heap`, `copy` or `ad-hoc`).
+    mode: String,
+    /// The profiled command.
+    cmd: String,
+    /// Whether the accesses to the blocks were tracked.
+    #[serde(default)]
+    bkacc: bool,
+    /// The threshold of short-lived blocks.
+    #[serde(default)]
+    tuth: u64,
+    /// The program points.
+    pps: Vec<RawProgramPoint>,
+    /// The frame table, which is referenced by the program points.
+    ftbl: Vec<String>,
+}
+
+/// The raw structure of a program point of the DHAT output file.
+#[derive(Debug, Deserialize)]
+struct RawProgramPoint {
+    /// The total number of bytes.
+    tb: u64,
+    /// The total number of blocks.
+    tbk: u64,
+    /// The total lifetime of all blocks.
+    #[serde(default)]
+    tl: u64,
+    /// The number of bytes read.
+    rb: Option<u64>,
+    /// The number of bytes written.
+    wb: Option<u64>,
+    /// The indices of the frames inside of the frame table.
+    fs: Vec<usize>,
+}
+
+/// Parse the contents of a DHAT output file.
+///
+/// Only the heap profiling mode of DHAT is supported.
+///
+/// # Errors
+/// A description of the problem is returned, if the output is malformed.
+pub fn parse(output: &str) -> Result<Dhat, String> {
+    let raw: RawDhat = serde_json::from_str(output).map_err(|e| e.to_string())?;
+    if raw.version != 2 {
+        return Err(format!("unsupported DHAT file version {}", raw.version));
+    }
+    if raw.mode != "heap" {
+        return Err(format!("unsupported DHAT mode `{}`", raw.mode));
+    }
+
+    // the first entry of the frame table is the `[root]` pseudo-frame
+    let frames: Vec<Option<Frame>> = raw
+        .ftbl
+        .iter()
+        .map(|frame| super::parse_frame(frame))
+        .collect();
+    let frame = |index: usize| {
+        frames
+            .get(index)
+            .cloned()
+            .ok_or_else(|| format!("invalid frame index {index}"))
+    };
+    let program_points = raw
+        .pps
+        .into_iter()
+        .map(|pp| {
+            let frames = pp
+                .fs
+                .iter()
+                .map(|&index| frame(index))
+                .filter_map(Result::transpose)
+                .collect::<Result<_, _>>()?;
+            Ok(ProgramPoint {
+                total_bytes: pp.tb,
+                total_blocks: pp.tbk,
+                total_lifetime: pp.tl,
+                read_bytes: pp.rb,
+                written_bytes: pp.wb,
+                frames,
+            })
+        })
+        .collect::<Result<_, String>>()?;
+
+    Ok(Dhat {
+        command: raw.cmd,
+        accesses_tracked: raw.bkacc,
+        short_lived_threshold: raw.tuth,
+        program_points,
+    })
+}
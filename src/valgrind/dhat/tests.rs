@@ -0,0 +1,39 @@
+use super::parse;
+use std::fs;
+
+#[test]
+fn program_points_are_parsed_with_demangled_frames() {
+    let dhat = fs::read_to_string("src/valgrind/dhat/dhat.out").expect("Could not open test file");
+    let dhat = parse(&dhat).expect("Could not read test file");
+    assert_eq!(dhat.command, "/project/target/debug/parser");
+    assert!(dhat.accesses_tracked);
+    assert_eq!(dhat.short_lived_threshold, 500);
+    assert_eq!(dhat.program_points.len(), 3);
+
+    let pp = &dhat.program_points[0];
+    assert_eq!(pp.total_bytes, 64000);
+    assert_eq!(pp.total_blocks, 1000);
+    assert_eq!(pp.average_lifetime(), 120);
+    assert_eq!(pp.read_bytes, Some(64000));
+    let functions: Vec<_> = pp
+        .frames
+        .iter()
+        .map(|frame| frame.function.as_deref())
+        .collect();
+    assert_eq!(
+        functions,
+        [
+            Some("malloc"),
+            Some("alloc::raw_vec::RawVec<T,A>::allocate_in"),
+            Some("parser::token::push"),
+            Some("parser::parse"),
+        ]
+    );
+    assert_eq!(pp.frames[3].line, Some(12));
+}
+
+#[test]
+fn other_modes_are_rejected() {
+    let dhat = r#"{"dhatFileVersion":2,"mode":"copy","cmd":"prog","pps":[],"ftbl":["[root]"]}"#;
+    assert!(parse(dhat).is_err());
+}
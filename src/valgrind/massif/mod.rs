@@ -0,0 +1,149 @@
+//! The output of the heap profiler massif (`massif.out.<pid>`).
+//!
+//! The format is described in the [massif manual][manual] and consists of a
+//! header and a list of snapshots. Some snapshots are detailed, i.e. contain a
+//! tree of the allocation sites.
+//!
+//! [manual]: https://valgrind.org/docs/manual/ms-manual.html
+
+#[cfg(test)]
+mod tests;
+
+use super::xml::Frame;
+
+/// A parsed massif output file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Massif {
+    /// The profiled command.
+    pub command: String,
+    /// All the snapshots taken during the run.
+    pub snapshots: Vec<Snapshot>,
+}
+impl Massif {
+    /// The snapshot with the peak heap usage.
+    ///
+    /// This is the snapshot marked as the peak by massif, if there is one, or
+    /// the snapshot with the largest heap usage otherwise.
+    pub fn peak(&self) -> Option<&Snapshot> {
+        self.snapshots
+            .iter()
+            .find(|snapshot| snapshot.is_peak)
+            .or_else(|| self.snapshots.iter().max_by_key(|snapshot| snapshot.heap))
+    }
+}
+
+/// The memory usage at a certain point in time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    /// The number of the snapshot.
+    pub index: usize,
+    /// The time of the snapshot (in the unit selected via `--time-unit`).
+    pub time: u64,
+    /// The number of useful heap bytes allocated.
+    pub heap: u64,
+    /// The number of bytes allocated in excess of what the program asked for,
+    /// i.e. administrative and alignment overhead.
+    pub heap_extra: u64,
+    /// The size of the stacks (only measured with `--stacks=yes`).
+    pub stacks: u64,
+    /// Whether this is the peak snapshot.
+    pub is_peak: bool,
+    /// The allocation tree of detailed snapshots.
+    pub tree: Option<Node>,
+}
+
+/// A node of the allocation tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Node {
+    /// The number of bytes allocated from this node (including all children).
+    pub bytes: u64,
+    /// The code location of this node.
+    ///
+    /// This is missing for the root node and for the summary of all allocation
+    /// sites below the significance threshold of massif.
+    pub frame: Option<Frame>,
+    /// The description of nodes without a frame.
+    pub description: String,
+    /// The callers allocating via this node.
+    pub children: Vec<Self>,
+}
+
+/// Parse the contents of a massif output file.
+///
+/// # Errors
+/// A description of the problem is returned, if the output is malformed.
+pub fn parse(output: &str) -> Result<Massif, String> {
+    let mut lines = output.lines().peekable();
+    let mut command = String::new();
+    let mut snapshots = Vec::new();
+    while let Some(line) = lines.next() {
+        if let Some(cmd) = line.strip_prefix("cmd: ") {
+            command = cmd.to_string();
+        } else if let Some(index) = line.strip_prefix("snapshot=") {
+            let index = parse_number(index, "snapshot")?;
+            let mut snapshot = Snapshot {
+                index,
+                time: 0,
+                heap: 0,
+                heap_extra: 0,
+                stacks: 0,
+                is_peak: false,
+                tree: None,
+            };
+            while let Some(line) = lines.next_if(|line| !line.starts_with("snapshot=")) {
+                let (key, value) = line.split_once('=').unwrap_or((line, ""));
+                match key {
+                    "time" => snapshot.time = parse_number(value, key)?,
+                    "mem_heap_B" => snapshot.heap = parse_number(value, key)?,
+                    "mem_heap_extra_B" => snapshot.heap_extra = parse_number(value, key)?,
+                    "mem_stacks_B" => snapshot.stacks = parse_number(value, key)?,
+                    "heap_tree" if value == "detailed" || value == "peak" => {
+                        snapshot.is_peak = value == "peak";
+                        snapshot.tree = Some(parse_node(&mut lines)?);
+                    }
+                    _ => {}
+                }
+            }
+            snapshots.push(snapshot);
+        }
+    }
+    Ok(Massif { command, snapshots })
+}
+
+/// Parse a node of the allocation tree including all of its children.
+///
+/// A node looks like `n<children>: <bytes> <location>`, indented by its depth.
+fn parse_node<'a, I: Iterator<Item = &'a str>>(lines: &mut I) -> Result<Node, String> {
+    let line = lines.next().ok_or("unexpected end of allocation tree")?;
+    let line = line.trim_start();
+    let (children, rest) = line
+        .strip_prefix('n')
+        .and_then(|line| line.split_once(": "))
+        .ok_or_else(|| format!("invalid allocation tree node `{line}`"))?;
+    let (bytes, location) = rest.split_once(' ').unwrap_or((rest, ""));
+    let children: usize = parse_number(children, "children")?;
+
+    let frame = super::parse_frame(location);
+    let mut node = Node {
+        bytes: parse_number(bytes, "bytes")?,
+        description: if frame.is_some() {
+            String::new()
+        } else {
+            location.to_string()
+        },
+        frame,
+        children: Vec::with_capacity(children),
+    };
+    for _ in 0..children {
+        node.children.push(parse_node(lines)?);
+    }
+    Ok(node)
+}
+
+/// Parse a number, describing a failure with the name of the value.
+fn parse_number<T: std::str::FromStr>(value: &str, name: &str) -> Result<T, String> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid value `{value}` for `{name}`"))
+}
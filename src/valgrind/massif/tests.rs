@@ -0,0 +1,54 @@
+use super::parse;
+use crate::valgrind::parse_frame;
+use std::fs;
+
+#[test]
+fn snapshots_and_peak_are_parsed() {
+    let massif =
+        fs::read_to_string("src/valgrind/massif/massif.out").expect("Could not open test file");
+    let massif = parse(&massif).expect("Could not read test file");
+    assert_eq!(massif.command, "/project/target/debug/service");
+    assert_eq!(massif.snapshots.len(), 4);
+
+    let peak = massif.peak().expect("there is a peak snapshot");
+    assert_eq!(peak.index, 2);
+    assert_eq!(peak.heap, 7168);
+    assert_eq!(peak.heap_extra, 56);
+
+    let tree = peak.tree.as_ref().expect("the peak is detailed");
+    assert_eq!(tree.bytes, 7168);
+    assert_eq!(tree.children.len(), 3);
+    assert_eq!(tree.children[0].children[0].bytes, 4096);
+    assert_eq!(
+        tree.children[2].description,
+        "in 2 places, all below massif's threshold (1.00%)"
+    );
+    assert!(tree.children[2].frame.is_none());
+}
+
+#[test]
+fn frames_are_parsed_and_demangled() {
+    let frame = parse_frame("0x10A1F1: service::main (main.rs:12)").expect("valid frame");
+    assert_eq!(frame.instruction_pointer, 0x10_A1F1);
+    assert_eq!(frame.function.as_deref(), Some("service::main"));
+    assert_eq!(frame.file.as_deref(), Some("main.rs"));
+    assert_eq!(frame.line, Some(12));
+
+    let frame =
+        parse_frame("0x10B111: _ZN7service5cache3new17h0123456789abcdefE (in /bin/service)")
+            .expect("valid frame");
+    assert_eq!(frame.function.as_deref(), Some("service::cache::new"));
+    assert_eq!(frame.object.as_deref(), Some("/bin/service"));
+
+    let frame = parse_frame("0x4C2FB0F: ??? (in /usr/lib/libc.so.6)").expect("valid frame");
+    assert_eq!(frame.function, None);
+
+    assert!(parse_frame("in 2 places, all below massif's threshold (1.00%)").is_none());
+}
+
+#[test]
+fn the_largest_snapshot_is_the_peak_without_explicit_peak() {
+    let massif = parse("cmd: prog\nsnapshot=0\nmem_heap_B=10\nheap_tree=empty\nsnapshot=1\nmem_heap_B=30\nheap_tree=empty\nsnapshot=2\nmem_heap_B=20\nheap_tree=empty\n")
+        .expect("valid massif output");
+    assert_eq!(massif.peak().map(|peak| peak.index), Some(1));
+}
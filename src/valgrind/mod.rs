@@ -1,18 +1,40 @@
 //! This module provides utility functions around valgrind.
 
+pub mod cachegrind;
+pub mod callgrind;
+pub mod channel;
+pub mod dhat;
+pub mod massif;
+pub mod stderr;
+pub mod stream;
+pub mod symbol;
 pub mod xml;
 
-use serde::Deserialize;
+#[cfg(test)]
+mod tests;
+
+use crate::archive::Archive;
+use crate::options;
+use channel::Channel;
 use std::ffi::OsString;
-use std::net::{SocketAddr, TcpListener};
+use std::fs::File;
+use std::io::{self, Read, Write};
 use std::process::Command;
-use std::{env, fmt, io::Read};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::{env, fmt};
 use std::{ffi::OsStr, process::Stdio};
 
 /// Part of the output message of `valgrind` if a possible stack overflow is
 /// detected.
 const STACK_OVERFLOW: &str = "main thread stack using the --main-stacksize= flag";
 
+/// The exit code of valgrind, if it detected errors in the program under test
+/// (`--error-exitcode`).
+///
+/// This is the same exit code as used by `cargo valgrind` for memory errors.
+const ERROR_EXIT_CODE: i32 = 127;
+
 /// Error type for valgrind-execution-related failures.
 #[derive(Debug)]
 pub enum Error {
@@ -20,13 +42,14 @@ pub enum Error {
     ///
     /// This is an user error.
     ValgrindNotInstalled,
-    /// Something around the socket creation did fail.
-    SocketConnection,
+    /// Something around the channel of the XML output (see [`channel`]) did
+    /// fail.
+    XmlChannel,
     /// The sub-process could not be waited on.
     ProcessFailed,
     /// Valgrind execution did fail.
     ///
-    /// The error output of valgrind is captured.
+    /// The messages of valgrind are captured (see [`stderr::Stderr`]).
     ValgrindFailure(String),
     /// A stack overflow was detected in the program under test.
     ///
@@ -37,7 +60,12 @@ pub enum Error {
     ///
     /// The error contains the signal number and the normal valgrind XML output
     /// (including any memory leaks if found until this point).
-    ProcessSignal(i32, xml::Output),
+    ProcessSignal(i32, Box<xml::Output>),
+    /// Valgrind did execute normally, but the run program exited with a
+    /// non-zero exit code (e.g. a failing test).
+    ///
+    /// The error contains the exit code and the normal valgrind XML output.
+    ProcessExit(i32, Box<xml::Output>),
     /// The valgrind output was malformed or otherwise unexpected.
     ///
     /// This variant contains the inner deserialization error and the output of
@@ -50,9 +78,10 @@ impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Self::ValgrindNotInstalled => write!(f, "valgrind executable not found"),
-            Self::SocketConnection => write!(f, "local TCP I/O error"),
+            Self::XmlChannel => write!(f, "cannot receive valgrind output"),
             Self::ProcessFailed => write!(f, "cannot start valgrind process"),
             Self::ProcessSignal(nr, _) => write!(f, "program exited with signal {nr}"),
+            Self::ProcessExit(code, _) => write!(f, "program exited with code {code}"),
             Self::StackOverflow(stderr) => write!(f, "stack overflow detected: {stderr}"),
             Self::ValgrindFailure(s) => write!(f, "invalid valgrind usage: {s}"),
             Self::MalformedOutput(e, _) => write!(f, "unexpected valgrind output: {e}"),
@@ -62,22 +91,44 @@ impl fmt::Display for Error {
 
 /// Execute a certain command inside of valgrind and collect the [`Output`].
 ///
+/// The output of the program on stderr is forwarded to the terminal while it
+/// runs (see [`stderr`]). Every error is passed to `on_error` as soon as
+/// valgrind reported it, i.e. while the program still runs (see [`stream`]).
+/// The errors are not collected, so the returned output never contains any.
+/// Errors of traced child processes are labeled with their [`Process`]. If an
+/// `archive` is given, the raw XML output and stderr are stored in it.
+/// Archiving is done on a best-effort basis, i.e. I/O errors are ignored.
+///
 /// [`Output`]: xml::Output
-pub fn execute<S, I>(command: I) -> Result<xml::Output, Error>
+/// [`Process`]: xml::Process
+pub fn execute<S, I, F>(
+    command: I,
+    archive: Option<&Archive>,
+    on_error: F,
+) -> Result<xml::Output, Error>
 where
     S: AsRef<OsStr>,
     I: IntoIterator<Item = S>,
+    F: FnMut(xml::Error) + Send + 'static,
 {
-    // open a TCP socket on localhost, port selected by OS
-    let address: SocketAddr = ([127, 0, 0, 1], 0).into();
-    let listener = TcpListener::bind(address).map_err(|_| Error::SocketConnection)?;
-    let address = listener.local_addr().map_err(|_| Error::SocketConnection)?;
-
+    // the selected tool is passed first, so that a `--tool` inside of the
+    // `VALGRINDFLAGS` takes precedence.
     let mut valgrind = Command::new("valgrind");
+    valgrind.arg(format!("--tool={}", options::tool().name()));
+    if options::check_fds() {
+        valgrind.arg("--track-fds=yes");
+    }
 
     // additional options to pass to valgrind?
-    if let Ok(additional_args) = env::var("VALGRINDFLAGS") {
-        valgrind.args(additional_args.split(' '));
+    let additional_args = additional_args();
+    valgrind.args(&additional_args);
+
+    // every traced child process sends its own XML output, which is also the
+    // case if it was enabled via the `VALGRINDFLAGS`.
+    let trace_children = options::trace_children()
+        || option_value(&additional_args, "--trace-children") == Some("yes");
+    if options::trace_children() {
+        valgrind.arg("--trace-children=yes");
     }
 
     // Apply the list of suppressions provided in the `suppressions` directory
@@ -94,76 +145,353 @@ where
         option
     });
 
-    let cargo = valgrind
+    // the XML output is received via a pipe or, if child processes are
+    // traced, via a socket on localhost (see `channel`).
+    let channel = Channel::open(trace_children)?;
+
+    // the dedicated exit code tells memory errors apart from the exit code of
+    // the program under test. It is passed last, so that it takes precedence.
+    let mut cargo = valgrind
         .arg("--xml=yes")
-        .arg(format!("--xml-socket={}:{}", address.ip(), address.port()))
+        .arg(channel.option()?)
+        .arg(format!("--error-exitcode={ERROR_EXIT_CODE}"))
         .args(command)
         .stderr(Stdio::piped())
         .spawn()
         .map_err(|_| Error::ValgrindNotInstalled)?;
-
-    // spawn a new thread, that receives the XML and parses it. This has to be
-    // a separate execution unit (a thread is currently used, but an `async`
-    // task would be suitable as well), as the `accept()` call blocks until the
-    // valgrind binary writes something to the TCP connection. This is normally
-    // fine, but if we consider errors, e.g. wrong command line flags, valgrind
-    // won't write anything to the connection, so the program will hang forever.
-    // The thread can simply be thrown away, if valgrind fails.
-    let xml = std::thread::spawn(move || {
-        // collect the output of valgrind
-        let (mut listener, _) = listener.accept().map_err(|_| Error::SocketConnection)?;
-        let mut output = Vec::new();
-        listener
-            .read_to_end(&mut output)
-            .map_err(|_| Error::SocketConnection)?;
-        let xml: xml::Output = xml::Output::deserialize(
-            &mut serde_xml_rs::Deserializer::new_from_reader(&*output)
-                .non_contiguous_seq_elements(true),
-        )
-        .map(|output_: xml::Output| {
-            let mut output = output_;
-            if let Some(err) = output.errors {
-                let new_err: Vec<xml::Error> = err
-                    .into_iter()
-                    .filter(|e| {
-                        !e.kind.is_leak() || e.resources.bytes > 0 || e.resources.blocks > 0
-                    })
-                    .collect();
-                if new_err.is_empty() {
-                    output.errors = None;
-                } else {
-                    output.errors = Some(new_err);
+    let stderr = stderr::forward(cargo.stderr.take().expect("stderr is piped"));
+    let pid = cargo.id();
+    let found = Arc::new(AtomicUsize::new(0));
+    let xml = {
+        let archive = archive.cloned();
+        let on_error = Mutex::new(on_error);
+        let found = Arc::clone(&found);
+        channel.receive(trace_children, move |index, stream| {
+            let mut stream = Archived {
+                reader: stream,
+                archive: archive.as_ref(),
+                index,
+                file: None,
+            };
+            let output = stream::parse(&mut stream, |output, mut error| {
+                if output.pid != Some(pid) {
+                    error.process = Some(xml::Process::of(output));
                 }
-            }
+                found.fetch_add(1, Ordering::Relaxed);
+                let mut on_error = on_error.lock().expect("Error-handler panicked");
+                on_error(error);
+            });
+            // valgrind has to be able to write the rest of a malformed output,
+            // which is archived for the bug report as well.
+            io::copy(&mut stream, &mut io::sink()).ok();
             output
         })
-        .map_err(|e| Error::MalformedOutput(e, output))?;
-        Ok(xml)
-    });
+    };
 
-    let output = cargo.wait_with_output().map_err(|_| Error::ProcessFailed)?;
-    if output.status.success() {
-        let xml = xml.join().expect("Reader-thread panicked")?;
-        Ok(xml)
-    } else if let Some(signal_nr) = is_terminated_by_signal(output.status) {
-        let xml = xml.join().expect("Reader-thread panicked")?;
+    let status = cargo.wait().map_err(|_| Error::ProcessFailed)?;
+    let stderr = stderr.finish();
+    if let Some(archive) = archive {
+        archive.store_stderr(&stderr.output).ok();
+    }
+    let Some(xml) = combine(xml.finish()?, pid)? else {
+        // valgrind exits before running the program, if it rejects its
+        // command line, i.e. there is no XML output at all.
+        return Err(Error::ValgrindFailure(stderr.messages));
+    };
 
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        if stderr.contains(STACK_OVERFLOW) {
-            Err(Error::StackOverflow(stderr.to_string()))
+    if status.success() {
+        Ok(xml)
+    } else if let Some(signal_nr) = is_terminated_by_signal(status) {
+        if stderr.mentions(STACK_OVERFLOW) {
+            Err(Error::StackOverflow(stderr.messages))
         } else {
-            Err(Error::ProcessSignal(signal_nr, xml))
+            Err(Error::ProcessSignal(signal_nr, Box::new(xml)))
         }
     } else {
-        // this does not really terminalte the thread, but detaches it. Despite
-        // that, the thread will be killed, if the main thread exits.
-        drop(xml);
-        Err(Error::ValgrindFailure(
-            String::from_utf8_lossy(&output.stderr).to_string(),
-        ))
+        // the program might exit with the dedicated exit code by itself, so
+        // it only indicates memory errors, if there are any.
+        match status.code() {
+            Some(ERROR_EXIT_CODE) if found.load(Ordering::Relaxed) > 0 => Ok(xml),
+            Some(code) => Err(Error::ProcessExit(code, Box::new(xml))),
+            None => Err(Error::ProcessFailed),
+        }
+    }
+}
+
+/// The additional options to pass to valgrind, which are given as a
+/// space-delimited list in the `VALGRINDFLAGS` (which may be empty, see
+/// [`split_args()`]).
+fn additional_args() -> Vec<String> {
+    split_args(&env::var("VALGRINDFLAGS").unwrap_or_default())
+}
+
+/// The `--error-exitcode` given in the `VALGRINDFLAGS`, if any.
+///
+/// It is ignored, since `cargo valgrind` relies on its own dedicated exit code
+/// to tell memory errors apart from the exit code of the program under test.
+pub fn ignored_error_exitcode() -> Option<String> {
+    option_value(&additional_args(), "--error-exitcode").map(String::from)
+}
+
+/// Split a whitespace-delimited list of options.
+///
+/// Parts of the options may be quoted with single or double quotes (e.g. to
+/// include a space), the quotes themselves are removed.
+fn split_args(args: &str) -> Vec<String> {
+    let mut split = Vec::new();
+    let mut current: Option<String> = None;
+    let mut quote = None;
+    for c in args.chars() {
+        match quote {
+            Some(quote_char) if c == quote_char => quote = None,
+            None if c == '"' || c == '\'' => {
+                quote = Some(c);
+                current.get_or_insert_with(String::new);
+            }
+            None if c.is_whitespace() => split.extend(current.take()),
+            _ => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+    split.extend(current);
+    split
+}
+
+/// The value of the option `name` (e.g. `--trace-children`) in the given
+/// options. If it is given multiple times, the last one is used like valgrind
+/// does.
+fn option_value<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    args.iter()
+        .rev()
+        .find_map(|arg| arg.strip_prefix(name)?.strip_prefix('='))
+}
+
+/// Combine the XML outputs of the program under test (with the process id
+/// `pid`) and its traced child processes into a single [`xml::Output`] with the
+/// outputs of the child processes as its [`children`](xml::Output::children).
+///
+/// Streams without any output (e.g. the wake-up connection of a socket) are
+/// ignored, i.e. `None` is returned, if valgrind did not send any output at all.
+fn combine(
+    outputs: Vec<Result<Option<xml::Output>, Error>>,
+    pid: u32,
+) -> Result<Option<xml::Output>, Error> {
+    let mut outputs = outputs
+        .into_iter()
+        .filter_map(Result::transpose)
+        .collect::<Result<Vec<_>, _>>()?;
+    if outputs.is_empty() {
+        return Ok(None);
     }
+    // the program under test is usually the first one to connect, but this is
+    // not guaranteed, so it is identified by its process id.
+    let main = outputs
+        .iter()
+        .position(|output| output.pid == Some(pid))
+        .unwrap_or(0);
+    let mut output = outputs.remove(main);
+    output.children = outputs;
+    Ok(Some(output))
+}
 
-    // TODO: use drop guard, that waits on child in order to prevent printing to stdout of the child
+/// A reader of the XML output, which writes everything read into the archive
+/// on a best-effort basis.
+///
+/// The archive file is only created once there is any output, so that e.g. the
+/// wake-up connection of a socket is not archived.
+struct Archived<'a, R> {
+    /// The XML output.
+    reader: R,
+    /// The archive to create the file in, until it is created.
+    archive: Option<&'a Archive>,
+    /// The number of the XML output (see [`Archive::create_xml()`]).
+    index: usize,
+    /// The archive file, if it was created successfully.
+    file: Option<File>,
+}
+impl<R: Read> Read for Archived<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let length = self.reader.read(buf)?;
+        if length > 0 {
+            if let Some(archive) = self.archive.take() {
+                self.file = archive.create_xml(self.index).ok();
+            }
+            if let Some(file) = &mut self.file {
+                file.write_all(&buf[..length]).ok();
+            }
+        }
+        Ok(length)
+    }
+}
+
+/// The way, in which the program under test exited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exit {
+    /// The program exited with the given exit code.
+    Code(i32),
+    /// The program was terminated by the given signal.
+    Signal(i32),
+}
+
+/// Execute a certain command inside of a profiling tool of valgrind and return
+/// the contents of the output file of the tool (e.g. `massif.out.<pid>`)
+/// together with the way, in which the program exited.
+///
+/// The stderr is forwarded like in [`execute()`]. If an `archive` is given, the
+/// output file and stderr are stored in it. Archiving is done on a best-effort
+/// basis, i.e. I/O errors are ignored.
+///
+/// # Errors
+/// Failures of running valgrind are reported like in [`execute()`], but a
+/// non-zero exit code of the program or a signal terminating it is not an
+/// error, since the profile is written anyway.
+pub fn profile<S, I>(
+    tool: options::Tool,
+    command: I,
+    archive: Option<&Archive>,
+) -> Result<(Vec<u8>, Exit), Error>
+where
+    S: AsRef<OsStr>,
+    I: IntoIterator<Item = S>,
+{
+    let output_file = temp_file::TempFile::with_prefix(tool.name())
+        .expect("could not create temporary profile output file");
+
+    let mut valgrind = Command::new("valgrind");
+    valgrind.arg(format!("--tool={}", tool.name()));
+    if tool == options::Tool::Cachegrind {
+        // the cache simulation is disabled by default since valgrind 3.21, but
+        // it can still be disabled again via the `VALGRINDFLAGS`.
+        valgrind.arg("--cache-sim=yes");
+    }
+    valgrind.args(additional_args());
+    let mut process = valgrind
+        .arg({
+            let mut option = OsString::from(format!("--{}-out-file=", tool.name()));
+            option.push(output_file.path());
+            option
+        })
+        .args(command)
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|_| Error::ValgrindNotInstalled)?;
+    let stderr = stderr::forward(process.stderr.take().expect("stderr is piped"));
+    let status = process.wait().map_err(|_| Error::ProcessFailed)?;
+    let stderr = stderr.finish();
+    if let Some(archive) = archive {
+        archive.store_stderr(&stderr.output).ok();
+    }
+
+    if stderr.mentions(STACK_OVERFLOW) {
+        return Err(Error::StackOverflow(stderr.messages));
+    }
+    let exit = if let Some(signal_nr) = is_terminated_by_signal(status) {
+        Exit::Signal(signal_nr)
+    } else {
+        match status.code() {
+            Some(code) if code != 0 && stderr.is_usage_error() => {
+                return Err(Error::ValgrindFailure(stderr.messages));
+            }
+            Some(code) => Exit::Code(code),
+            None => return Err(Error::ProcessFailed),
+        }
+    };
+    let profile = std::fs::read(output_file.path()).map_err(|_| Error::ProcessFailed)?;
+    if let Some(archive) = archive {
+        archive.store_profile(tool.name(), &profile).ok();
+    }
+    Ok((profile, exit))
+}
+
+/// Parse the complete XML output of valgrind.
+///
+/// The errors are filtered, demangled and normalized like in
+/// [`stream::parse()`], but collected into the output.
+///
+/// # Errors
+/// This function returns [`Error::MalformedOutput`], if the XML could not be
+/// parsed, e.g. since it is empty.
+pub fn parse(xml: &[u8]) -> Result<xml::Output, Error> {
+    let mut errors = Vec::new();
+    let mut output = stream::parse(xml, |_, error| errors.push(error))?.ok_or_else(|| {
+        let error = <serde_xml_rs::Error as serde::de::Error>::custom("empty XML output");
+        Error::MalformedOutput(error, xml.to_vec())
+    })?;
+    output.errors = if errors.is_empty() {
+        None
+    } else {
+        Some(errors)
+    };
+    Ok(output)
+}
+
+/// Parse a code location of the text-based output of the profilers like
+/// `0x10A0F1: my_crate::main (main.rs:5)` or `0x4C2FB0F: malloc (in
+/// /usr/lib/libc.so)`. The location may contain a column as well, which is
+/// ignored (`main.rs:5:13`).
+///
+/// The function name is demangled.
+pub fn parse_frame(location: &str) -> Option<xml::Frame> {
+    let (address, rest) = location.split_once(": ")?;
+    let instruction_pointer = u64::from_str_radix(address.strip_prefix("0x")?, 16).ok()?;
+
+    let (function, source) = match rest
+        .strip_suffix(')')
+        .and_then(|rest| rest.rsplit_once(" ("))
+    {
+        Some((function, source)) => (function, Some(source)),
+        None => (rest, None),
+    };
+    let mut frame = xml::Frame {
+        instruction_pointer,
+        object: None,
+        directory: None,
+        function: Some(function)
+            .filter(|function| *function != "???")
+            .map(symbol::demangle),
+        file: None,
+        line: None,
+    };
+    match source {
+        Some(source) if source.starts_with("in ") => frame.object = Some(source[3..].to_string()),
+        Some(source) => {
+            let mut parts = source.split(':');
+            frame.file = parts.next().map(String::from);
+            frame.line = parts.next().and_then(|line| line.parse().ok());
+        }
+        None => {}
+    }
+    Some(frame)
+}
+
+/// Parse the costs of a cost line of the callgrind or cachegrind output.
+fn parse_costs<'a, I: Iterator<Item = &'a str>>(costs: I) -> Result<Vec<u64>, String> {
+    costs
+        .map(|cost| cost.parse().map_err(|_| format!("invalid cost `{cost}`")))
+        .collect()
+}
+
+/// Add the costs to the accumulated costs, extending them as necessary.
+fn add_costs(accumulated: &mut Vec<u64>, costs: &[u64]) {
+    if accumulated.len() < costs.len() {
+        accumulated.resize(costs.len(), 0);
+    }
+    for (accumulated, cost) in accumulated.iter_mut().zip(costs) {
+        *accumulated += cost;
+    }
+}
+
+/// Demangle the function names of all frames of the error.
+fn demangle(mut error: xml::Error) -> xml::Error {
+    for frame in error
+        .stack_trace
+        .iter_mut()
+        .flat_map(|stack| &mut stack.frames)
+    {
+        if let Some(function) = &mut frame.function {
+            *function = symbol::demangle(function);
+        }
+    }
+    error
 }
 
 /// Check if the given exit status is caued by program termination via a signal.
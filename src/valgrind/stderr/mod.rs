@@ -0,0 +1,146 @@
+//! Forwarding of the stderr shared by valgrind and the program under test.
+//!
+//! The output of the program (e.g. panic messages or the failures of libtest)
+//! is forwarded to the terminal while the program runs. The messages of
+//! valgrind itself are recognized by their prefix (e.g. `==1234==`) and kept
+//! separately instead, so that they can be inspected after valgrind exited.
+
+#[cfg(test)]
+mod tests;
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The time without any further output, after which the forwarding is stopped
+/// once valgrind exited (see [`Forwarding::finish()`]).
+const DRAIN_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// The maximum time, for which the remaining output is forwarded once valgrind
+/// exited (see [`Forwarding::finish()`]).
+const DRAIN_LIMIT: Duration = Duration::from_secs(2);
+
+/// The captured stderr of a valgrind run.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Stderr {
+    /// The complete stderr including the output of the program.
+    pub output: Vec<u8>,
+    /// The messages of valgrind itself, one per line.
+    pub messages: String,
+}
+impl Stderr {
+    /// Query, whether any message of valgrind contains the given text.
+    pub fn mentions(&self, text: &str) -> bool {
+        self.messages.contains(text)
+    }
+
+    /// Query, whether valgrind reported a fatal error, e.g. an invalid command
+    /// line option, instead of running the program.
+    pub fn is_usage_error(&self) -> bool {
+        self.messages
+            .lines()
+            .any(|message| message.starts_with("valgrind:"))
+    }
+}
+
+/// The forwarding of stderr in a separate thread (see [`forward()`]).
+#[derive(Debug)]
+pub struct Forwarding {
+    /// The stderr captured so far.
+    captured: Arc<Mutex<Stderr>>,
+    /// Signals the progress of the forwarding and is closed, once the stream
+    /// is closed.
+    progress: Receiver<()>,
+}
+impl Forwarding {
+    /// Stop the forwarding after valgrind exited and return the captured
+    /// [`Stderr`].
+    ///
+    /// The stream is normally closed right after valgrind exited. A child
+    /// process (e.g. a daemon) might keep it open, though, so the remaining
+    /// output is only forwarded until there was no output for a short time
+    /// (or for a limited time at most). The thread is detached then, i.e. it
+    /// keeps forwarding the output of the child process to the terminal.
+    pub fn finish(self) -> Stderr {
+        // stops, once the stream is closed or no output was received in time
+        let deadline = Instant::now() + DRAIN_LIMIT;
+        while Instant::now() < deadline && self.progress.recv_timeout(DRAIN_TIMEOUT).is_ok() {}
+        let captured = self.captured.lock().expect("Stderr-thread panicked");
+        captured.clone()
+    }
+}
+
+/// Forward the stderr of valgrind to the terminal in a separate thread.
+///
+/// The thread finishes as soon as the stream is closed, i.e. when valgrind and
+/// all (traced) child processes exited. The captured [`Stderr`] is returned by
+/// [`Forwarding::finish()`].
+pub fn forward<R: Read + Send + 'static>(stderr: R) -> Forwarding {
+    let captured = Arc::new(Mutex::new(Stderr::default()));
+    let (sender, progress) = mpsc::sync_channel(1);
+    {
+        let captured = Arc::clone(&captured);
+        thread::spawn(move || {
+            forward_to(BufReader::new(stderr), io::stderr(), &captured, || {
+                // the progress only has to be noticed, not every single line
+                sender.try_send(()).ok();
+            });
+        });
+    }
+    Forwarding { captured, progress }
+}
+
+/// Forward the output of the program from `stderr` to the `terminal` line by
+/// line and collect the messages of valgrind into `captured`. The `progress` is
+/// called after every line.
+///
+/// Errors during writing to the terminal are ignored, since the output is
+/// captured anyway, and reading stops at the first error.
+fn forward_to<R, W, F>(mut stderr: R, mut terminal: W, captured: &Mutex<Stderr>, mut progress: F)
+where
+    R: BufRead,
+    W: Write,
+    F: FnMut(),
+{
+    let mut line = Vec::new();
+    while stderr
+        .read_until(b'\n', &mut line)
+        .is_ok_and(|length| length > 0)
+    {
+        let text = String::from_utf8_lossy(&line);
+        let is_message = is_valgrind_message(&text);
+        if !is_message {
+            terminal
+                .write_all(&line)
+                .and_then(|()| terminal.flush())
+                .ok();
+        }
+        {
+            let mut captured = captured.lock().expect("Stderr-handler panicked");
+            if is_message {
+                captured.messages.push_str(&text);
+                if !text.ends_with('\n') {
+                    captured.messages.push('\n');
+                }
+            }
+            captured.output.append(&mut line);
+        }
+        progress();
+    }
+}
+
+/// Check, whether a line of stderr was written by valgrind itself.
+///
+/// Valgrind prefixes its messages with the process id enclosed in `==`, `--`
+/// or `**` (e.g. `==1234== Invalid read`), while fatal errors like invalid
+/// command line options start with `valgrind:`.
+pub fn is_valgrind_message(line: &str) -> bool {
+    line.starts_with("valgrind:")
+        || ["==", "--", "**"].iter().any(|marker| {
+            line.strip_prefix(marker)
+                .and_then(|rest| rest.split_once(marker))
+                .is_some_and(|(pid, _)| !pid.is_empty() && pid.bytes().all(|c| c.is_ascii_digit()))
+        })
+}
@@ -0,0 +1,91 @@
+use super::{forward, forward_to, is_valgrind_message, Stderr};
+use std::io::{self, Read};
+use std::sync::{mpsc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Forward the given stderr and return the captured one.
+fn capture<W: io::Write>(stderr: &str, terminal: W) -> Stderr {
+    let captured = Mutex::default();
+    forward_to(stderr.as_bytes(), terminal, &captured, || {});
+    captured.into_inner().expect("Stderr-handler panicked")
+}
+
+#[test]
+fn messages_of_valgrind_are_recognized_by_their_prefix() {
+    assert!(is_valgrind_message(
+        "==1234== Memcheck, a memory error detector"
+    ));
+    assert!(is_valgrind_message("--1234-- WARNING: unhandled syscall"));
+    assert!(is_valgrind_message("**1234** VG_(malloc): out of memory"));
+    assert!(is_valgrind_message("valgrind: Bad option: --foo"));
+
+    assert!(!is_valgrind_message(
+        "thread 'main' panicked at src/main.rs:2:5:"
+    ));
+    assert!(!is_valgrind_message("---- tests::it_works stdout ----"));
+    assert!(!is_valgrind_message("==== separator ===="));
+    assert!(!is_valgrind_message("==12a== not a process id"));
+}
+
+#[test]
+fn program_output_is_forwarded_and_valgrind_messages_are_kept() {
+    let stderr = "\
+        ==42== Warning: client switching stacks?\n\
+        thread 'main' panicked at src/main.rs:2:5:\n\
+        ==42== If you believe this happened as a result of a stack\n\
+        oops";
+    let mut terminal = Vec::new();
+    let captured = capture(stderr, &mut terminal);
+
+    assert_eq!(
+        String::from_utf8_lossy(&terminal),
+        "thread 'main' panicked at src/main.rs:2:5:\noops"
+    );
+    assert_eq!(
+        captured.messages,
+        "==42== Warning: client switching stacks?\n\
+         ==42== If you believe this happened as a result of a stack\n"
+    );
+    assert_eq!(captured.output, stderr.as_bytes());
+    assert!(captured.mentions("as a result of a stack"));
+    assert!(!captured.is_usage_error());
+}
+
+#[test]
+fn fatal_errors_of_valgrind_are_usage_errors() {
+    let stderr = "valgrind: Bad option: --foo\nvalgrind: Use --help for more information.\n";
+    let captured = capture(stderr, Vec::new());
+    assert!(captured.is_usage_error());
+}
+
+/// A stream, that is kept open after the data was read (e.g. by a daemon).
+struct OpenStream {
+    data: &'static [u8],
+    open: mpsc::Receiver<()>,
+}
+impl Read for OpenStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.data.is_empty() {
+            self.open.recv().ok(); // blocks until the test is done
+            return Ok(0);
+        }
+        let length = buf.len().min(self.data.len());
+        buf[..length].copy_from_slice(&self.data[..length]);
+        self.data = &self.data[length..];
+        Ok(length)
+    }
+}
+
+#[test]
+fn forwarding_stops_if_the_stream_is_kept_open() {
+    let (close, open) = mpsc::channel();
+    let forwarding = forward(OpenStream {
+        data: b"==42== HEAP SUMMARY:\n",
+        open,
+    });
+    let start = Instant::now();
+    let captured = forwarding.finish();
+    assert!(start.elapsed() < Duration::from_secs(5));
+    assert_eq!(captured.messages, "==42== HEAP SUMMARY:\n");
+    close.send(()).ok();
+}
@@ -0,0 +1,255 @@
+//! Incremental parsing of the XML output of valgrind.
+//!
+//! The XML output is parsed while it is received, so that every error can be
+//! reported as soon as valgrind wrote it instead of after the program exited.
+//! The preamble of the document (the protocol, the process and the command
+//! line) is parsed once, when the first element starts. Afterwards each
+//! `<error>` and `<announcethread>` element is cut out of the document and
+//! parsed on its own, while the rest of the document (e.g. the status and the
+//! error counts) is dropped. Neither the raw XML nor the parsed errors are kept, so that the
+//! memory does not grow with the number of errors.
+
+#[cfg(test)]
+mod tests;
+
+use super::{demangle, xml, Error};
+use serde::de::DeserializeOwned;
+use std::io::{self, Read};
+
+/// The number of bytes read from the XML output at once.
+const CHUNK_SIZE: usize = 8 * 1024;
+
+/// The start tag of an error.
+const ERROR_START: &[u8] = b"<error>";
+
+/// The end tag of an error.
+const ERROR_END: &[u8] = b"</error>";
+
+/// The start tag of a thread announced by helgrind.
+const THREAD_START: &[u8] = b"<announcethread>";
+
+/// The end tag of a thread announced by helgrind.
+const THREAD_END: &[u8] = b"</announcethread>";
+
+/// The end tag of the whole document.
+const DOCUMENT_END: &[u8] = b"</valgrindoutput>";
+
+/// Parse the XML output of valgrind from the reader while it is received.
+///
+/// Every error is passed to `on_error` as soon as it is complete together with
+/// the output received so far. The errors are not collected, i.e. the returned
+/// output never contains any errors. Leak errors without any leaked bytes or
+/// blocks are skipped, since they don't indicate any actual leak. The same
+/// applies to file descriptors inherited from the parent process (e.g. the
+/// standard streams or the jobserver pipes of cargo), which are reported
+/// without a stack trace. The function names of all frames are demangled and
+/// the output of the thread error detectors is brought into the shape of the
+/// memcheck output (see [`xml::Output::normalize()`]).
+///
+/// `None` is returned, if the reader did not provide any output at all.
+///
+/// # Errors
+/// This function returns [`Error::MalformedOutput`], if the XML could not be
+/// parsed or is incomplete, and [`Error::XmlChannel`], if it could not be read.
+pub fn parse<R, F>(mut reader: R, mut on_error: F) -> Result<Option<xml::Output>, Error>
+where
+    R: Read,
+    F: FnMut(&xml::Output, xml::Error),
+{
+    let mut splitter = Splitter::default();
+    let mut output = None;
+    let mut chunk = vec![0; CHUNK_SIZE];
+    loop {
+        let length = match reader.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(length) => length,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(_) => return Err(Error::XmlChannel),
+        };
+        for part in splitter.push(&chunk[..length]) {
+            match part {
+                Part::Header(header) => output = Some(parse_header(&header)?),
+                Part::Thread(thread) => {
+                    let output = output.as_mut().expect("header is parsed first");
+                    output.announce(deserialize(&thread)?);
+                }
+                Part::Error(error) => {
+                    let output = output.as_ref().expect("header is parsed first");
+                    if let Some(error) = parse_error(output, &error)? {
+                        on_error(output, error);
+                    }
+                }
+            }
+        }
+    }
+
+    match splitter.finish() {
+        // a document without any errors or threads is parsed as a whole
+        Rest::Document(document) if document.iter().all(u8::is_ascii_whitespace) => Ok(None),
+        Rest::Document(document) => deserialize(&document).map(Some),
+        Rest::Incomplete(tail) => {
+            let error = <serde_xml_rs::Error as serde::de::Error>::custom("incomplete XML output");
+            Err(Error::MalformedOutput(error, tail))
+        }
+        Rest::Complete => Ok(output),
+    }
+}
+
+/// Parse the preamble of the document received before the first element, which
+/// is closed for that purpose.
+fn parse_header(document: &[u8]) -> Result<xml::Output, Error> {
+    let mut header = document.to_vec();
+    header.extend_from_slice(DOCUMENT_END);
+    deserialize(&header)
+}
+
+/// Parse a single error of the given output.
+///
+/// `None` is returned, if the error doesn't indicate an actual problem (see
+/// [`parse()`]).
+fn parse_error(output: &xml::Output, error: &[u8]) -> Result<Option<xml::Error>, Error> {
+    let mut error: xml::Error = deserialize(error)?;
+    output.normalize(&mut error);
+
+    let no_leak = error.kind.is_leak() && error.resources.bytes == 0 && error.resources.blocks == 0;
+    let inherited_fd = error.kind.is_fd_leak() && error.stack_trace.is_empty();
+    if no_leak || inherited_fd {
+        Ok(None)
+    } else {
+        Ok(Some(demangle(error)))
+    }
+}
+
+/// Deserialize a (part of the) XML output.
+fn deserialize<T: DeserializeOwned>(xml: &[u8]) -> Result<T, Error> {
+    T::deserialize(
+        &mut serde_xml_rs::Deserializer::new_from_reader(xml).non_contiguous_seq_elements(true),
+    )
+    .map_err(|e| Error::MalformedOutput(e, xml.to_vec()))
+}
+
+/// A part of the XML document, which is parsed on its own.
+#[derive(Debug, PartialEq, Eq)]
+enum Part {
+    /// The document before the first error or thread.
+    Header(Vec<u8>),
+    /// A single `<error>` element.
+    Error(Vec<u8>),
+    /// A single `<announcethread>` element.
+    Thread(Vec<u8>),
+}
+
+/// The remainder of the XML document after all of it was received.
+#[derive(Debug, PartialEq, Eq)]
+enum Rest {
+    /// The whole document, since it didn't contain any error or thread.
+    Document(Vec<u8>),
+    /// The document ended within an element or before its end tag. Only the
+    /// incomplete element or the last bytes are kept.
+    Incomplete(Vec<u8>),
+    /// The document was complete.
+    Complete,
+}
+
+/// Splits the XML document into its header, errors and threads regardless of
+/// the chunks, in which the document is received. Everything else after the
+/// header is dropped, only the end of the document is looked out for.
+#[derive(Debug, Default)]
+struct Splitter {
+    /// The received bytes, which are not assigned to a part yet.
+    pending: Vec<u8>,
+    /// The document received before the first element.
+    header: Vec<u8>,
+    /// Whether the header was split off, i.e. an element was started.
+    split: bool,
+    /// The end tag of the element, that the pending bytes belong to.
+    element: Option<&'static [u8]>,
+    /// The number of pending bytes of an element, which were already searched
+    /// for its end.
+    searched: usize,
+    /// Whether the end of the document was received.
+    complete: bool,
+}
+impl Splitter {
+    /// Add the next received bytes and return the parts completed by them.
+    fn push(&mut self, bytes: &[u8]) -> Vec<Part> {
+        self.pending.extend_from_slice(bytes);
+        let mut parts = Vec::new();
+        loop {
+            if let Some(end_tag) = self.element {
+                // valgrind writes line by line, so the end of an element is
+                // only searched in the newly received bytes.
+                let Some(end) = find(&self.pending[self.searched..], end_tag) else {
+                    self.searched = self.pending.len().saturating_sub(end_tag.len() - 1);
+                    break;
+                };
+                let end = self.searched + end + end_tag.len();
+                let element = self.pending.drain(..end).collect();
+                parts.push(if end_tag == ERROR_END {
+                    Part::Error(element)
+                } else {
+                    Part::Thread(element)
+                });
+                self.element = None;
+                self.searched = 0;
+            } else if let Some((start, end_tag)) = self.next_element() {
+                self.skip(start);
+                if !self.split {
+                    parts.push(Part::Header(std::mem::take(&mut self.header)));
+                    self.split = true;
+                }
+                self.element = Some(end_tag);
+            } else {
+                // the last bytes might be the beginning of the next element or
+                // of the end tag of the document.
+                let keep = self.pending.len().min(DOCUMENT_END.len() - 1);
+                self.skip(self.pending.len() - keep);
+                break;
+            }
+        }
+        parts
+    }
+
+    /// Search the start of the next element in the pending bytes and return
+    /// its position together with its end tag.
+    fn next_element(&self) -> Option<(usize, &'static [u8])> {
+        let error = find(&self.pending, ERROR_START).map(|start| (start, ERROR_END));
+        let thread = find(&self.pending, THREAD_START).map(|start| (start, THREAD_END));
+        error.into_iter().chain(thread).min()
+    }
+
+    /// Remove the first `length` pending bytes, which don't belong to any
+    /// element. They are added to the header, until it is split off, and
+    /// dropped afterwards.
+    fn skip(&mut self, length: usize) {
+        if self.split {
+            // the end tag might start within the skipped bytes
+            let end = self.pending.len().min(length + DOCUMENT_END.len() - 1);
+            self.complete |= find(&self.pending[..end], DOCUMENT_END).is_some();
+            self.pending.drain(..length);
+        } else {
+            self.header.extend(self.pending.drain(..length));
+        }
+    }
+
+    /// Finish the splitting and return the remainder of the document.
+    fn finish(mut self) -> Rest {
+        if !self.split {
+            self.header.append(&mut self.pending);
+            Rest::Document(self.header)
+        } else if self.element.is_none()
+            && (self.complete || find(&self.pending, DOCUMENT_END).is_some())
+        {
+            Rest::Complete
+        } else {
+            Rest::Incomplete(self.pending)
+        }
+    }
+}
+
+/// Search the first occurrence of the `needle` in the `haystack`.
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
@@ -0,0 +1,173 @@
+use super::{parse, Rest, Splitter, DOCUMENT_END};
+use crate::valgrind::{self, Error};
+use std::cell::Cell;
+use std::fs;
+use std::io::{self, Read};
+
+/// A reader returning the data in chunks of the given size, which counts the
+/// bytes read so far.
+struct Chunked<'a> {
+    data: &'a [u8],
+    size: usize,
+    position: &'a Cell<usize>,
+}
+impl Read for Chunked<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let length = self.size.min(buf.len()).min(self.data.len());
+        buf[..length].copy_from_slice(&self.data[..length]);
+        self.data = &self.data[length..];
+        self.position.set(self.position.get() + length);
+        Ok(length)
+    }
+}
+
+#[test]
+fn errors_are_reported_before_the_output_is_complete() {
+    let xml = fs::read("src/valgrind/xml/version4.xml").expect("Could not open test file");
+    let position = Cell::new(0);
+    let reader = Chunked {
+        data: &xml,
+        size: 64,
+        position: &position,
+    };
+
+    let mut reported = Vec::new();
+    let output = parse(reader, |_, error| {
+        reported.push((position.get(), error));
+    })
+    .expect("Could not read test file")
+    .expect("There is output in the test file");
+    assert!(output.errors.is_none());
+
+    let errors = valgrind::parse(&xml)
+        .expect("Could not read test file")
+        .errors
+        .expect("There are errors in the test case");
+    assert_eq!(reported.len(), errors.len());
+    for ((position, reported), error) in reported.iter().zip(&errors) {
+        assert!(*position < xml.len());
+        assert_eq!(reported, error);
+    }
+    // the leaks are reported one after another while they are received
+    assert!(reported.windows(2).all(|pair| pair[0].0 < pair[1].0));
+}
+
+#[test]
+fn output_does_not_depend_on_the_chunks() {
+    for file in ["version4.xml", "helgrind.xml", "drd.xml", "fds.xml"] {
+        let xml = fs::read(format!("src/valgrind/xml/{file}")).expect("Could not open test file");
+        let mut complete_errors = Vec::new();
+        let complete = parse(&xml[..], |_, error| complete_errors.push(error))
+            .expect("Could not read test file");
+        for size in [1, 7, 100] {
+            let reader = Chunked {
+                data: &xml,
+                size,
+                position: &Cell::new(0),
+            };
+            let mut errors = Vec::new();
+            let chunked =
+                parse(reader, |_, error| errors.push(error)).expect("Could not read test file");
+            assert_eq!(chunked, complete, "{file} in chunks of {size} bytes");
+            assert_eq!(errors, complete_errors, "{file} in chunks of {size} bytes");
+        }
+    }
+}
+
+#[test]
+fn errors_are_reported_with_the_output_received_so_far() {
+    let xml = fs::read("src/valgrind/xml/fds.xml").expect("Could not open test file");
+    let mut pids = Vec::new();
+    parse(&xml[..], |output, _| {
+        assert!(output.errors.is_none());
+        pids.push(output.pid);
+    })
+    .expect("Could not read test file");
+    assert_eq!(pids, [Some(21407); 3]);
+}
+
+#[test]
+fn no_output_is_not_an_error() {
+    let output = parse(&b""[..], |_, _| panic!("there are no errors"));
+    assert!(matches!(output, Ok(None)));
+}
+
+#[test]
+fn truncated_output_is_malformed() {
+    let xml = fs::read("src/valgrind/xml/version4.xml").expect("Could not open test file");
+    let end = xml
+        .windows(b"</error>".len())
+        .position(|window| window == b"</error>")
+        .expect("There are errors in the test case");
+    let output = parse(&xml[..end], |_, _| {});
+    assert!(matches!(output, Err(Error::MalformedOutput(..))));
+}
+
+#[test]
+fn output_without_end_is_malformed() {
+    let xml = fs::read("src/valgrind/xml/version4.xml").expect("Could not open test file");
+    let end = xml
+        .windows(DOCUMENT_END.len())
+        .position(|window| window == DOCUMENT_END)
+        .expect("The test file is complete");
+    let output = parse(&xml[..end], |_, _| {});
+    assert!(matches!(output, Err(Error::MalformedOutput(..))));
+}
+
+#[test]
+fn threads_are_announced_between_errors() {
+    let xml =
+        fs::read_to_string("src/valgrind/xml/helgrind.xml").expect("Could not open test file");
+    // move the announcement of the second thread behind a copy of the error
+    let thread = xml.rfind("<announcethread>").expect("There are threads");
+    let thread_end = xml[thread..]
+        .find("</announcethread>")
+        .expect("The thread is complete")
+        + thread
+        + "</announcethread>".len();
+    let error = xml.find("<error>").expect("There is an error");
+    let error_end = xml.find("</error>").expect("The error is complete") + "</error>".len();
+    let xml = [
+        &xml[..thread],
+        &xml[error..error_end],
+        &xml[thread..thread_end],
+        &xml[thread_end..],
+    ]
+    .concat();
+
+    let mut stack_traces = Vec::new();
+    parse(xml.as_bytes(), |_, error| {
+        stack_traces.push(error.stack_trace.len());
+    })
+    .expect("Could not read test file");
+    // the creation of the second thread is only known for the second error
+    assert_eq!(stack_traces, [2, 3]);
+}
+
+#[test]
+fn received_bytes_are_not_kept() {
+    let xml = fs::read("src/valgrind/xml/version4.xml").expect("Could not open test file");
+    let errors_start = xml
+        .windows(b"<error>".len())
+        .position(|window| window == b"<error>")
+        .expect("There are errors in the test case");
+    let last_error_end = xml
+        .windows(b"</error>".len())
+        .rposition(|window| window == b"</error>")
+        .expect("There are errors in the test case")
+        + b"</error>".len();
+
+    let mut splitter = Splitter::default();
+    splitter.push(&xml[..errors_start]);
+    let mut parts = 0;
+    for _ in 0..1000 {
+        parts += splitter.push(&xml[errors_start..last_error_end]).len();
+        assert!(splitter.header.is_empty());
+        assert!(splitter.pending.len() < DOCUMENT_END.len());
+    }
+    splitter.push(&xml[last_error_end..]);
+    assert!(splitter.pending.len() < DOCUMENT_END.len());
+    assert_eq!(splitter.finish(), Rest::Complete);
+    // the header and all the errors
+    assert_eq!(parts, 1 + 1000 * 8);
+}
@@ -0,0 +1,78 @@
+//! Cleaning up the symbol names reported by valgrind.
+//!
+//! Valgrind reports the function of a frame either demangled (if debug info is
+//! available) or as the raw symbol name. Rust symbols can be mangled with the
+//! legacy (`_ZN…17h<hash>E`) or the v0 scheme (`_R…`). Both are demangled and
+//! the hash suffix is stripped, since it carries no information for the user.
+
+#[cfg(test)]
+mod tests;
+
+/// The length of the hash suffix of legacy symbols, e.g. `h0123456789abcdef`.
+const HASH_LENGTH: usize = 17;
+
+/// Demangle a Rust symbol name and strip its hash.
+///
+/// Names, that are not mangled, are returned as is, except for a trailing hash
+/// segment (`::h0123456789abcdef`), which valgrind leaves in place when it
+/// demangles legacy symbols itself.
+pub fn demangle(name: &str) -> String {
+    if let Ok(symbol) = rustc_demangle::try_demangle(name) {
+        return format!("{symbol:#}");
+    }
+    strip_hash(name).to_string()
+}
+
+/// Strip the trailing hash segment of an already demangled legacy symbol.
+fn strip_hash(name: &str) -> &str {
+    name.rsplit_once("::")
+        .filter(|(_, hash)| {
+            hash.len() == HASH_LENGTH
+                && hash.starts_with('h')
+                && hash[1..].chars().all(|c| c.is_ascii_hexdigit())
+        })
+        .map_or(name, |(path, _)| path)
+}
+
+/// Replace the generic arguments of a (demangled) name by `<…>`.
+///
+/// Qualified paths like `<alloc::vec::Vec<T> as core::ops::Drop>::drop` are
+/// kept, only the generic arguments inside of them are elided, which results
+/// in `<alloc::vec::Vec<…> as core::ops::Drop>::drop`.
+pub fn elide_generics(name: &str) -> String {
+    let mut elided = String::with_capacity(name.len());
+    // for every currently open `<`, whether it starts generic arguments
+    let mut brackets: Vec<bool> = Vec::new();
+    let mut previous = None;
+    for c in name.chars() {
+        let is_eliding = brackets.contains(&true);
+        match c {
+            '<' => {
+                let is_generic = previous.is_some_and(|previous: char| {
+                    previous.is_alphanumeric() || "_:>".contains(previous)
+                });
+                if is_generic && !is_eliding {
+                    // strip the `::` of a turbofish, as it is noise without arguments
+                    if elided.ends_with("::") {
+                        elided.truncate(elided.len() - 2);
+                    }
+                    elided.push_str("<…>");
+                } else if !is_eliding {
+                    elided.push(c);
+                }
+                brackets.push(is_generic);
+            }
+            // `->` of function pointer types does not close a bracket
+            '>' if previous != Some('-') && !brackets.is_empty() => {
+                let was_generic = brackets.pop() == Some(true);
+                if !was_generic && !is_eliding {
+                    elided.push(c);
+                }
+            }
+            c if !is_eliding => elided.push(c),
+            _ => {}
+        }
+        previous = Some(c);
+    }
+    elided
+}
@@ -0,0 +1,53 @@
+use super::{demangle, elide_generics};
+
+#[test]
+fn legacy_symbols_are_demangled_without_hash() {
+    assert_eq!(
+        demangle("_ZN4core3ptr13drop_in_place17h0123456789abcdefE"),
+        "core::ptr::drop_in_place"
+    );
+}
+
+#[test]
+fn v0_symbols_are_demangled_without_hash() {
+    assert_eq!(demangle("_RNvCs1234_7mycrate4main"), "mycrate::main");
+}
+
+#[test]
+fn hash_of_demangled_symbols_is_stripped() {
+    assert_eq!(
+        demangle("std::rt::lang_start::h0123456789abcdef"),
+        "std::rt::lang_start"
+    );
+    assert_eq!(demangle("std::rt::hello"), "std::rt::hello");
+}
+
+#[test]
+fn other_symbols_are_kept() {
+    assert_eq!(demangle("malloc"), "malloc");
+    assert_eq!(demangle("_dl_catch_exception"), "_dl_catch_exception");
+}
+
+#[test]
+fn generic_arguments_are_elided() {
+    assert_eq!(
+        elide_generics("reserve_internal<u8,alloc::alloc::Global>"),
+        "reserve_internal<…>"
+    );
+    assert_eq!(
+        elide_generics("core::ptr::drop_in_place::<alloc::vec::Vec<u8>>"),
+        "core::ptr::drop_in_place<…>"
+    );
+    assert_eq!(elide_generics("map<fn() -> u8, u8>"), "map<…>");
+}
+
+#[test]
+fn qualified_paths_are_kept() {
+    assert_eq!(
+        elide_generics(
+            "<core::iter::adapters::Map<I,F> as core::iter::traits::iterator::Iterator>::fold::{{closure}}"
+        ),
+        "<core::iter::adapters::Map<…> as core::iter::traits::iterator::Iterator>::fold::{{closure}}"
+    );
+    assert_eq!(elide_generics("malloc"), "malloc");
+}
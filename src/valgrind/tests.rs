@@ -0,0 +1,34 @@
+use super::{option_value, split_args};
+
+#[test]
+fn options_are_split_at_whitespace() {
+    assert_eq!(
+        split_args(" --tool=memcheck  --leak-check=full\t-v "),
+        ["--tool=memcheck", "--leak-check=full", "-v"]
+    );
+    assert!(split_args("").is_empty());
+}
+
+#[test]
+fn quotes_are_removed_from_options() {
+    assert_eq!(
+        split_args(
+            r#"--trace-children-skip="/bin/sh,*/my tool" '--trace-children=yes' --log-file=''"#
+        ),
+        [
+            "--trace-children-skip=/bin/sh,*/my tool",
+            "--trace-children=yes",
+            "--log-file="
+        ]
+    );
+}
+
+#[test]
+fn last_occurrence_of_an_option_is_used() {
+    let args = split_args("--trace-children=no --leak-check=full --trace-children=yes");
+    assert_eq!(option_value(&args, "--trace-children"), Some("yes"));
+    let args = split_args("--trace-children=yes --trace-children=no");
+    assert_eq!(option_value(&args, "--trace-children"), Some("no"));
+    assert_eq!(option_value(&args, "--trace"), None);
+    assert_eq!(option_value(&args, "--error-exitcode"), None);
+}
@@ -1,7 +1,8 @@
 //! A module containing the structure of the valgrind XML output.
 //!
-//! Only the memcheck tool is implemented in accordance to [this][link]
-//! description].
+//! Only the memcheck tool and the thread error detectors helgrind and DRD are
+//! implemented in accordance to [this][link] description. The errors of the
+//! file descriptor tracking (`--track-fds=yes`) are supported as well.
 //!
 //! Note, that not all fields are implemented.
 //!
@@ -11,8 +12,10 @@
 #[cfg(test)]
 mod tests;
 
-use serde::{de::Visitor, Deserialize, Deserializer};
+use serde::{de::Visitor, Deserialize, Deserializer, Serialize, Serializer};
+use std::convert::TryFrom;
 use std::fmt::{self, Display, Formatter};
+use std::path::PathBuf;
 
 /// The output of a valgrind run.
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize)]
@@ -22,8 +25,108 @@ pub struct Output {
     protocol_version: ProtocolVersion,
     #[serde(rename = "protocoltool")]
     tool: Tool,
+    /// The process id of the analyzed program.
+    #[serde(default)]
+    pub pid: Option<u32>,
+    /// The process id of the parent of the analyzed program.
+    #[serde(default)]
+    pub ppid: Option<u32>,
+    #[serde(default)]
+    pub args: Option<Arguments>,
     #[serde(rename = "error")]
     pub errors: Option<Vec<Error>>,
+    /// The threads announced by helgrind, which are referenced by its errors.
+    #[serde(default)]
+    #[serde(rename = "announcethread")]
+    threads: Vec<Thread>,
+    /// The outputs of the child processes traced via `--trace-children=yes`,
+    /// which are received as separate XML documents.
+    #[serde(skip)]
+    pub children: Vec<Self>,
+}
+impl Output {
+    /// The protocol version of the output, if it is newer than the supported
+    /// ones and therefore only parsed on a best-effort basis.
+    pub const fn newer_protocol_version(&self) -> Option<u32> {
+        match self.protocol_version {
+            ProtocolVersion::Newer(version) => Some(version),
+            ProtocolVersion::Version4 | ProtocolVersion::Version5 | ProtocolVersion::Version6 => {
+                None
+            }
+        }
+    }
+
+    /// Add a thread announced by helgrind, which is referenced by the
+    /// following errors.
+    pub fn announce(&mut self, thread: Thread) {
+        self.threads.push(thread);
+    }
+
+    /// Bring an error of this output into the same shape for all tools.
+    ///
+    /// The thread error detectors use additional elements for the messages and
+    /// stack traces, which are merged into the fields used by memcheck. The
+    /// creation of every thread involved in a helgrind error is added as an
+    /// additional stack trace, if it was announced in this output.
+    pub fn normalize(&self, error: &mut Error) {
+        error.normalize(&self.threads);
+    }
+}
+
+/// A thread announced by helgrind before it is referenced by an error.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize)]
+pub struct Thread {
+    #[serde(rename = "hthreadid")]
+    id: u64,
+    /// The stack trace of the thread creation (missing for the root thread).
+    #[serde(default)]
+    stack: Option<Stack>,
+}
+
+/// The command lines of valgrind and the program under test.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize)]
+pub struct Arguments {
+    #[serde(rename = "argv")]
+    pub program: CommandLine,
+}
+
+/// A command line consisting of the executable and its arguments.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize)]
+pub struct CommandLine {
+    pub exe: String,
+    #[serde(default)]
+    #[serde(rename = "arg")]
+    pub arguments: Vec<String>,
+}
+
+/// A traced child process of the program under test (see
+/// [`Output::children`]).
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct Process {
+    /// The process id of the child process.
+    pub pid: Option<u32>,
+    /// The process id of its parent process.
+    pub ppid: Option<u32>,
+    /// The command line of the child process separated by spaces.
+    pub command: String,
+}
+impl Process {
+    /// Describe the process of the given output.
+    pub fn of(output: &Output) -> Self {
+        let command = output.args.as_ref().map_or_else(String::new, |args| {
+            let program = &args.program;
+            std::iter::once(&program.exe)
+                .chain(&program.arguments)
+                .map(String::as_str)
+                .collect::<Vec<_>>()
+                .join(" ")
+        });
+        Self {
+            pid: output.pid,
+            ppid: output.ppid,
+            command,
+        }
+    }
 }
 
 /// The version of the XML format.
@@ -32,65 +135,267 @@ pub struct Output {
 /// and newer, so that all older formats will fail. The other `struct`s in this
 /// file assume the newer protocol versions, which are largely compatible.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(try_from = "String")]
 enum ProtocolVersion {
     // older formats are not supported and won't be in the future
     /// Protocol [version 4].
     ///
     /// [version 4]: https://sourceware.org/git/?p=valgrind.git;a=blob_plain;f=docs/internals/xml-output-protocol4.txt;hb=d772e25995c3400eecf2b6070e0bf3411447c3d1
-    #[serde(rename = "4")]
     Version4,
     /// Protocol [version 5].
     ///
     /// [version 5]: https://sourceware.org/git/?p=valgrind.git;a=blob_plain;f=docs/internals/xml-output-protocol5.txt;hb=48d64d0e6bb72220bb2557be4f427a57038dfbc6
-    #[serde(rename = "5")]
     Version5,
     /// Protocol [version 6].
     ///
     /// [version 6]: https://sourceware.org/git/?p=valgrind.git;a=blob_plain;f=docs/internals/xml-output-protocol6.txt;hb=7786b075abef51ca3d84b9717915f04b32950b32
-    #[serde(rename = "6")]
     Version6,
-    // newer versions are not yet supported! Feel free to add one via a PR.
+    /// A newer protocol version, which is not known yet.
+    ///
+    /// It is parsed on a best-effort basis assuming it is compatible with the
+    /// latest known version. Feel free to add a proper variant via a PR.
+    Newer(u32),
+}
+impl TryFrom<String> for ProtocolVersion {
+    type Error = String;
+
+    fn try_from(version: String) -> Result<Self, Self::Error> {
+        match version.trim().parse() {
+            Ok(4) => Ok(Self::Version4),
+            Ok(5) => Ok(Self::Version5),
+            Ok(6) => Ok(Self::Version6),
+            Ok(version) if version > 6 => Ok(Self::Newer(version)),
+            _ => Err(format!("unsupported protocol version `{version}`")),
+        }
+    }
 }
 
 /// The check tool used by valgrind.
 ///
-/// Although there are other tools available, there is only a variant for the
-/// so-called `memcheck` tool, so that all other tools will fail. The other
-/// `struct`s in this file assume the memcheck output.
+/// Although there are other tools available, there are only variants for the
+/// so-called `memcheck` tool and the thread error detectors, so that all other
+/// tools will fail. The other `struct`s in this file assume the memcheck
+/// output, the output of the thread error detectors is converted into that
+/// shape by [`Output::normalize()`].
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
 enum Tool {
     #[serde(rename = "memcheck")]
     MemCheck,
+    #[serde(rename = "helgrind")]
+    Helgrind,
+    #[serde(rename = "drd")]
+    Drd,
     // other tools are not supported
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(from = "RawError")]
 pub struct Error {
-    #[serde(deserialize_with = "deserialize_hex")]
+    #[serde(serialize_with = "serialize_hex")]
     unique: u64,
     pub kind: Kind,
-    #[serde(default)]
-    #[serde(rename = "xwhat")]
     pub resources: Resources,
-    #[serde(default)]
-    #[serde(rename = "what")]
+    /// The file descriptor of the errors reported with `--track-fds`.
+    pub fd: Option<i32>,
+    /// The path of the file referred to by the file descriptor, if any.
+    pub path: Option<String>,
     pub main_info: Option<String>,
-    #[serde(default)]
-    #[serde(rename = "auxwhat")]
     pub auxiliary_info: Vec<String>,
-    #[serde(rename = "stack")]
+    /// The stack traces of the error, which are only missing for file
+    /// descriptors inherited from the parent process.
     pub stack_trace: Vec<Stack>,
+    pub thread_name: Option<String>,
+    /// The child process, in which the error occurred, if it was not the
+    /// program under test itself (see [`Process::of()`]).
+    pub process: Option<Process>,
+    /// The message and the thread of a thread error (see
+    /// [`Output::normalize()`]).
+    #[serde(skip_serializing)]
+    extended_info: ExtendedInfo,
+    /// The auxiliary messages of thread errors, which describe the additional
+    /// stack traces (see [`Output::normalize()`]).
+    #[serde(skip_serializing)]
+    extended_auxiliary_info: Vec<ExtendedInfo>,
+    /// The start of the conflicting segment of a DRD error.
+    #[serde(skip_serializing)]
+    other_segment_start: Option<Segment>,
+    /// The end of the conflicting segment of a DRD error.
+    #[serde(skip_serializing)]
+    other_segment_end: Option<Segment>,
 }
+impl From<RawError> for Error {
+    fn from(raw: RawError) -> Self {
+        let extended = raw.extended.unwrap_or_default();
+        Self {
+            unique: raw.unique,
+            kind: raw.kind,
+            resources: Resources {
+                bytes: extended.bytes,
+                blocks: extended.blocks,
+            },
+            fd: raw.fd,
+            path: raw.path,
+            main_info: raw.main_info,
+            auxiliary_info: raw.auxiliary_info,
+            stack_trace: raw.stack_trace,
+            thread_name: raw.thread_name,
+            process: raw.process,
+            extended_info: ExtendedInfo {
+                text: extended.text,
+                thread_id: extended.thread_id,
+            },
+            extended_auxiliary_info: raw.extended_auxiliary_info,
+            other_segment_start: raw.other_segment_start,
+            other_segment_end: raw.other_segment_end,
+        }
+    }
+}
+impl Error {
+    /// Merge the tool-specific elements into the common fields.
+    ///
+    /// See [`Output::normalize()`] for details.
+    fn normalize(&mut self, threads: &[Thread]) {
+        let extended_auxiliary_info = std::mem::take(&mut self.extended_auxiliary_info);
+        let thread_ids: Vec<u64> = self
+            .extended_info
+            .thread_id
+            .into_iter()
+            .chain(
+                extended_auxiliary_info
+                    .iter()
+                    .filter_map(|info| info.thread_id),
+            )
+            .collect();
+        if self.main_info.is_none() {
+            self.main_info = self.extended_info.text.take();
+        }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+        // The extended auxiliary information describe the stack traces after
+        // the first one, while the plain ones describe the address. Therefore
+        // the former are put in front, so that they line up with the stacks.
+        let texts = extended_auxiliary_info
+            .into_iter()
+            .filter_map(|info| info.text);
+        self.auxiliary_info = texts.chain(self.auxiliary_info.drain(..)).collect();
+
+        let segments = [
+            (
+                "start of the conflicting segment",
+                self.other_segment_start.take(),
+            ),
+            (
+                "end of the conflicting segment",
+                self.other_segment_end.take(),
+            ),
+        ];
+        for (message, segment) in segments {
+            if let Some(stack) = segment.and_then(|segment| segment.stack) {
+                self.push_stack(message.into(), stack);
+            }
+        }
+        for id in thread_ids {
+            let creation = threads.iter().find(|thread| thread.id == id);
+            if let Some(stack) = creation.and_then(|thread| thread.stack.clone()) {
+                self.push_stack(format!("thread #{id} was created"), stack);
+            }
+        }
+    }
+
+    /// Add another stack trace described by `message`.
+    ///
+    /// The message is inserted right after the auxiliary information of the
+    /// existing stack traces, since any further ones don't belong to a stack.
+    fn push_stack(&mut self, message: String, stack: Stack) {
+        let index = self
+            .stack_trace
+            .len()
+            .saturating_sub(1)
+            .min(self.auxiliary_info.len());
+        self.auxiliary_info.insert(index, message);
+        self.stack_trace.push(stack);
+    }
+}
+
+/// An error as written by valgrind, which is converted into an [`Error`].
+///
+/// The extended information (`<xwhat>`) holds the resources lost by a leak for
+/// memcheck, but the message and the thread of the error for the thread error
+/// detectors, which are kept apart from the [`Resources`].
+#[derive(Deserialize)]
+struct RawError {
+    #[serde(deserialize_with = "deserialize_hex")]
+    unique: u64,
+    kind: Kind,
+    #[serde(default)]
+    #[serde(rename(deserialize = "xwhat"), alias = "resources")]
+    extended: Option<RawExtendedInfo>,
+    #[serde(default)]
+    fd: Option<i32>,
+    #[serde(default)]
+    path: Option<String>,
+    #[serde(default)]
+    #[serde(rename(deserialize = "what"), alias = "main_info")]
+    main_info: Option<String>,
+    #[serde(default)]
+    #[serde(rename(deserialize = "auxwhat"), alias = "auxiliary_info")]
+    auxiliary_info: Vec<String>,
+    #[serde(default)]
+    #[serde(rename(deserialize = "stack"), alias = "stack_trace")]
+    stack_trace: Vec<Stack>,
+    #[serde(default)]
+    #[serde(rename(deserialize = "threadname"), alias = "thread_name")]
+    thread_name: Option<String>,
+    #[serde(default)]
+    process: Option<Process>,
+    #[serde(default, rename = "xauxwhat")]
+    extended_auxiliary_info: Vec<ExtendedInfo>,
+    #[serde(default)]
+    other_segment_start: Option<Segment>,
+    #[serde(default)]
+    other_segment_end: Option<Segment>,
+}
+
+/// The extended information of an error as written by the different tools.
+#[derive(Default, Deserialize)]
+struct RawExtendedInfo {
+    #[serde(default)]
+    #[serde(rename(deserialize = "leakedbytes"), alias = "bytes")]
+    bytes: usize,
+    #[serde(default)]
+    #[serde(rename(deserialize = "leakedblocks"), alias = "blocks")]
+    blocks: usize,
+    #[serde(default)]
+    text: Option<String>,
+    #[serde(default, rename = "hthreadid")]
+    thread_id: Option<u64>,
+}
+
+/// A message of a thread error, which may reference a thread.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash, Deserialize)]
+struct ExtendedInfo {
+    #[serde(default)]
+    text: Option<String>,
+    #[serde(default, rename = "hthreadid")]
+    thread_id: Option<u64>,
+}
+
+/// A segment of a thread, i.e. the code executed between two synchronization
+/// operations, as reported by DRD.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize)]
+struct Segment {
+    #[serde(default)]
+    stack: Option<Stack>,
+}
+
+/// The kind of an error.
+///
+/// Kinds unknown to this program (e.g. of newer valgrind versions) are kept as
+/// [`Kind::Other`], so that they can still be reported.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Kind {
-    #[serde(rename = "Leak_DefinitelyLost")]
     LeakDefinitelyLost,
-    #[serde(rename = "Leak_StillReachable")]
     LeakStillReachable,
-    #[serde(rename = "Leak_IndirectlyLost")]
     LeakIndirectlyLost,
-    #[serde(rename = "Leak_PossiblyLost")]
     LeakPossiblyLost,
     InvalidFree,
     MismatchedFree,
@@ -103,10 +408,155 @@ pub enum Kind {
     UninitValue,
     SyscallParam,
     ClientCheck,
+    CoreMem,
+    FishyValue,
+    ReallocSizeZero,
+    InvalidAlignment,
+    InvalidSize,
+    MismatchedSize,
+    MismatchedAlignment,
+    // errors of helgrind
+    Race,
+    UnlockUnlocked,
+    UnlockForeign,
+    UnlockBogus,
+    PthApiError,
+    LockOrder,
+    Misc,
+    // errors of DRD
+    ConflictingAccess,
+    MutexErr,
+    CondErr,
+    CondDestrErr,
+    CondRaceErr,
+    CondWaitErr,
+    SemaphoreErr,
+    BarrierErr,
+    RwLockErr,
+    HoldtimeErr,
+    GenericErr,
+    InvalidThreadId,
+    UnimpHgClReq,
+    UnimpDrdClReq,
+    // errors of the file descriptor tracking (`--track-fds`)
+    FdNotClosed,
+    FdBadClose,
+    /// Any other error kind, which is not known (yet).
+    Other(String),
 }
 impl Kind {
+    /// The identifier of the error kind as used by valgrind, e.g. `InvalidRead`.
+    pub fn id(&self) -> &str {
+        match self {
+            Self::LeakDefinitelyLost => "Leak_DefinitelyLost",
+            Self::LeakStillReachable => "Leak_StillReachable",
+            Self::LeakIndirectlyLost => "Leak_IndirectlyLost",
+            Self::LeakPossiblyLost => "Leak_PossiblyLost",
+            Self::InvalidFree => "InvalidFree",
+            Self::MismatchedFree => "MismatchedFree",
+            Self::InvalidRead => "InvalidRead",
+            Self::InvalidWrite => "InvalidWrite",
+            Self::InvalidJump => "InvalidJump",
+            Self::Overlap => "Overlap",
+            Self::InvalidMemPool => "InvalidMemPool",
+            Self::UninitCondition => "UninitCondition",
+            Self::UninitValue => "UninitValue",
+            Self::SyscallParam => "SyscallParam",
+            Self::ClientCheck => "ClientCheck",
+            Self::CoreMem => "CoreMemError",
+            Self::FishyValue => "FishyValue",
+            Self::ReallocSizeZero => "ReallocSizeZero",
+            Self::InvalidAlignment => "InvalidAlignment",
+            Self::InvalidSize => "InvalidSize",
+            Self::MismatchedSize => "MismatchedAllocateDeallocateSize",
+            Self::MismatchedAlignment => "MismatchedAllocateDeallocateAlignment",
+            Self::Race => "Race",
+            Self::UnlockUnlocked => "UnlockUnlocked",
+            Self::UnlockForeign => "UnlockForeign",
+            Self::UnlockBogus => "UnlockBogus",
+            Self::PthApiError => "PthAPIerror",
+            Self::LockOrder => "LockOrder",
+            Self::Misc => "Misc",
+            Self::ConflictingAccess => "ConflictingAccess",
+            Self::MutexErr => "MutexErr",
+            Self::CondErr => "CondErr",
+            Self::CondDestrErr => "CondDestrErr",
+            Self::CondRaceErr => "CondRaceErr",
+            Self::CondWaitErr => "CondWaitErr",
+            Self::SemaphoreErr => "SemaphoreErr",
+            Self::BarrierErr => "BarrierErr",
+            Self::RwLockErr => "RwLockErr",
+            Self::HoldtimeErr => "HoldtimeErr",
+            Self::GenericErr => "GenericErr",
+            Self::InvalidThreadId => "InvalidThreadId",
+            Self::UnimpHgClReq => "UnimpHgClReq",
+            Self::UnimpDrdClReq => "UnimpDrdClReq",
+            Self::FdNotClosed => "FdNotClosed",
+            Self::FdBadClose => "FdBadClose",
+            Self::Other(id) => id,
+        }
+    }
+
+    /// The error kind with the given identifier as used by valgrind.
+    fn from_id(id: String) -> Self {
+        match id.as_str() {
+            "Leak_DefinitelyLost" => Self::LeakDefinitelyLost,
+            "Leak_StillReachable" => Self::LeakStillReachable,
+            "Leak_IndirectlyLost" => Self::LeakIndirectlyLost,
+            "Leak_PossiblyLost" => Self::LeakPossiblyLost,
+            "InvalidFree" => Self::InvalidFree,
+            "MismatchedFree" => Self::MismatchedFree,
+            "InvalidRead" => Self::InvalidRead,
+            "InvalidWrite" => Self::InvalidWrite,
+            "InvalidJump" => Self::InvalidJump,
+            "Overlap" => Self::Overlap,
+            "InvalidMemPool" => Self::InvalidMemPool,
+            "UninitCondition" => Self::UninitCondition,
+            "UninitValue" => Self::UninitValue,
+            "SyscallParam" => Self::SyscallParam,
+            "ClientCheck" => Self::ClientCheck,
+            "CoreMemError" => Self::CoreMem,
+            "FishyValue" => Self::FishyValue,
+            "ReallocSizeZero" => Self::ReallocSizeZero,
+            "InvalidAlignment" => Self::InvalidAlignment,
+            "InvalidSize" => Self::InvalidSize,
+            "MismatchedAllocateDeallocateSize" => Self::MismatchedSize,
+            "MismatchedAllocateDeallocateAlignment" => Self::MismatchedAlignment,
+            "Race" => Self::Race,
+            "UnlockUnlocked" => Self::UnlockUnlocked,
+            "UnlockForeign" => Self::UnlockForeign,
+            "UnlockBogus" => Self::UnlockBogus,
+            "PthAPIerror" => Self::PthApiError,
+            "LockOrder" => Self::LockOrder,
+            "Misc" => Self::Misc,
+            "ConflictingAccess" => Self::ConflictingAccess,
+            "MutexErr" => Self::MutexErr,
+            "CondErr" => Self::CondErr,
+            "CondDestrErr" => Self::CondDestrErr,
+            "CondRaceErr" => Self::CondRaceErr,
+            "CondWaitErr" => Self::CondWaitErr,
+            "SemaphoreErr" => Self::SemaphoreErr,
+            "BarrierErr" => Self::BarrierErr,
+            "RwLockErr" => Self::RwLockErr,
+            "HoldtimeErr" => Self::HoldtimeErr,
+            "GenericErr" => Self::GenericErr,
+            "InvalidThreadId" => Self::InvalidThreadId,
+            "UnimpHgClReq" => Self::UnimpHgClReq,
+            "UnimpDrdClReq" => Self::UnimpDrdClReq,
+            "FdNotClosed" => Self::FdNotClosed,
+            "FdBadClose" => Self::FdBadClose,
+            _ => Self::Other(id),
+        }
+    }
+
+    /// Query, if the current error kind is a file descriptor, that was not
+    /// closed.
+    pub(crate) const fn is_fd_leak(&self) -> bool {
+        matches!(self, Self::FdNotClosed)
+    }
+
     /// Query, if the current error kind is a memory leak
-    pub(crate) const fn is_leak(self) -> bool {
+    pub(crate) const fn is_leak(&self) -> bool {
         match self {
             Self::LeakDefinitelyLost
             | Self::LeakStillReachable
@@ -122,10 +572,51 @@ impl Kind {
             | Self::UninitCondition
             | Self::UninitValue
             | Self::SyscallParam
-            | Self::ClientCheck => false,
+            | Self::ClientCheck
+            | Self::CoreMem
+            | Self::FishyValue
+            | Self::ReallocSizeZero
+            | Self::InvalidAlignment
+            | Self::InvalidSize
+            | Self::MismatchedSize
+            | Self::MismatchedAlignment
+            | Self::Race
+            | Self::UnlockUnlocked
+            | Self::UnlockForeign
+            | Self::UnlockBogus
+            | Self::PthApiError
+            | Self::LockOrder
+            | Self::Misc
+            | Self::ConflictingAccess
+            | Self::MutexErr
+            | Self::CondErr
+            | Self::CondDestrErr
+            | Self::CondRaceErr
+            | Self::CondWaitErr
+            | Self::SemaphoreErr
+            | Self::BarrierErr
+            | Self::RwLockErr
+            | Self::HoldtimeErr
+            | Self::GenericErr
+            | Self::InvalidThreadId
+            | Self::UnimpHgClReq
+            | Self::UnimpDrdClReq
+            | Self::FdNotClosed
+            | Self::FdBadClose
+            | Self::Other(_) => false,
         }
     }
 }
+impl<'de> Deserialize<'de> for Kind {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Self::from_id)
+    }
+}
+impl Serialize for Kind {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.id())
+    }
+}
 impl Display for Kind {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         match self {
@@ -144,38 +635,80 @@ impl Display for Kind {
             Self::UninitValue => write!(f, "uninitialized value"),
             Self::SyscallParam => write!(f, "syscall parameter"),
             Self::ClientCheck => write!(f, "client check"),
+            Self::CoreMem => write!(f, "uninitialized memory passed to the kernel"),
+            Self::FishyValue => write!(f, "fishy argument value"),
+            Self::ReallocSizeZero => write!(f, "realloc with size zero"),
+            Self::InvalidAlignment => write!(f, "invalid alignment"),
+            Self::InvalidSize => write!(f, "invalid size"),
+            Self::MismatchedSize => write!(f, "mismatched deallocation size"),
+            Self::MismatchedAlignment => write!(f, "mismatched deallocation alignment"),
+            Self::Race => write!(f, "data race"),
+            Self::UnlockUnlocked => write!(f, "unlock of an unlocked lock"),
+            Self::UnlockForeign => write!(f, "unlock of a lock held by another thread"),
+            Self::UnlockBogus => write!(f, "unlock of an invalid lock"),
+            Self::PthApiError => write!(f, "pthread API error"),
+            Self::LockOrder => write!(f, "lock order violation"),
+            Self::Misc => write!(f, "miscellaneous thread error"),
+            Self::ConflictingAccess => write!(f, "conflicting access"),
+            Self::MutexErr => write!(f, "mutex error"),
+            Self::CondErr => write!(f, "condition variable error"),
+            Self::CondDestrErr => write!(f, "destruction of a condition variable in use"),
+            Self::CondRaceErr => write!(f, "condition variable race"),
+            Self::CondWaitErr => write!(f, "condition variable wait error"),
+            Self::SemaphoreErr => write!(f, "semaphore error"),
+            Self::BarrierErr => write!(f, "barrier error"),
+            Self::RwLockErr => write!(f, "reader-writer lock error"),
+            Self::HoldtimeErr => write!(f, "lock held too long"),
+            Self::GenericErr => write!(f, "thread error"),
+            Self::InvalidThreadId => write!(f, "invalid thread id"),
+            Self::UnimpHgClReq => write!(f, "unimplemented helgrind client request"),
+            Self::UnimpDrdClReq => write!(f, "unimplemented DRD client request"),
+            Self::FdNotClosed => write!(f, "file descriptor leak"),
+            Self::FdBadClose => write!(f, "invalid close of a file descriptor"),
+            Self::Other(kind) => f.write_str(kind),
         }
     }
 }
 
-#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
 pub struct Resources {
-    #[serde(rename = "leakedbytes")]
+    #[serde(rename(deserialize = "leakedbytes"), alias = "bytes")]
     pub bytes: usize,
-    #[serde(rename = "leakedblocks")]
+    #[serde(rename(deserialize = "leakedblocks"), alias = "blocks")]
     pub blocks: usize,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
 pub struct Stack {
-    #[serde(rename = "frame")]
+    #[serde(rename(deserialize = "frame"), alias = "frames")]
     pub frames: Vec<Frame>,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
 pub struct Frame {
-    #[serde(rename = "ip")]
+    #[serde(rename(deserialize = "ip"), alias = "instruction_pointer")]
     #[serde(deserialize_with = "deserialize_hex")]
+    #[serde(serialize_with = "serialize_hex")]
     pub instruction_pointer: u64,
-    #[serde(rename = "obj")]
+    #[serde(rename(deserialize = "obj"), alias = "object")]
     pub object: Option<String>,
-    #[serde(rename = "dir")]
+    #[serde(rename(deserialize = "dir"), alias = "directory")]
     pub directory: Option<String>,
-    #[serde(rename = "fn")]
+    #[serde(rename(deserialize = "fn"), alias = "function")]
     pub function: Option<String>,
     pub file: Option<String>,
     pub line: Option<usize>,
 }
+impl Frame {
+    /// The full path of the source file of this frame, if known.
+    pub fn path(&self) -> Option<PathBuf> {
+        let file = self.file.as_ref()?;
+        Some(self.directory.as_ref().map_or_else(
+            || PathBuf::from(file),
+            |directory| PathBuf::from(directory).join(file),
+        ))
+    }
+}
 impl Display for Frame {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         f.write_str(self.function.as_ref().map_or("unknown", |s| s.as_str()))?;
@@ -191,6 +724,11 @@ impl Display for Frame {
     }
 }
 
+#[allow(clippy::trivially_copy_pass_by_ref)] // signature required by serde
+fn serialize_hex<S: Serializer>(value: &u64, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&format!("{value:#x}"))
+}
+
 fn deserialize_hex<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
     deserializer.deserialize_str(HexVisitor)
 }
@@ -226,3 +226,194 @@ fn unique_id_is_64bit() {
     .expect("Could not parse test XML");
     assert_eq!(result.unique, 0x1234_5678_9ABC_DEF0);
 }
+
+#[test]
+fn errors_are_serialized_with_descriptive_names() {
+    let error: Error = from_str(
+        "<error>\
+           <unique>0x1f</unique>\
+           <tid>1</tid>\
+           <kind>Leak_DefinitelyLost</kind>\
+           <xwhat>\
+           <text>...</text>\
+             <leakedbytes>15</leakedbytes>\
+             <leakedblocks>1</leakedblocks>\
+           </xwhat>\
+           <stack>\
+             <frame>\
+               <ip>0x483AD7B</ip>\
+               <fn>main</fn>\
+             </frame>\
+           </stack>\
+         </error>",
+    )
+    .expect("Could not parse test XML");
+
+    let json = serde_json::to_value(&error).expect("Could not serialize error");
+    assert_eq!(json["unique"], "0x1f");
+    assert_eq!(json["kind"], "Leak_DefinitelyLost");
+    assert_eq!(json["resources"]["bytes"], 15);
+    assert_eq!(json["resources"]["blocks"], 1);
+    assert_eq!(
+        json["stack_trace"][0]["frames"][0]["instruction_pointer"],
+        "0x483ad7b"
+    );
+    assert_eq!(json["stack_trace"][0]["frames"][0]["function"], "main");
+    assert!(json["stack_trace"][0]["frames"][0]["file"].is_null());
+}
+
+#[test]
+fn helgrind_races_contain_both_stacks_and_the_thread_creation() {
+    let xml = fs::read("src/valgrind/xml/helgrind.xml").expect("Could not open test file");
+    let xml = crate::valgrind::parse(&xml).expect("Could not read test file");
+    assert_eq!(xml.tool, super::Tool::Helgrind);
+
+    let errors = xml.errors.expect("There are errors in the test case");
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].kind, Kind::Race);
+    assert_eq!(
+        errors[0].main_info.as_deref(),
+        Some("Possible data race during read of size 4 at 0x15C03C by thread #1")
+    );
+    assert_eq!(
+        errors[0].auxiliary_info,
+        [
+            "This conflicts with a previous write of size 4 by thread #2",
+            "thread #2 was created",
+            "Location 0x15c03c is 0 bytes inside data symbol \"_ZN4race7COUNTER17h0123456789abcdefE\"",
+        ]
+    );
+    assert_eq!(errors[0].stack_trace.len(), 3);
+    assert_eq!(
+        errors[0].stack_trace[2].frames[2].function.as_deref(),
+        Some("std::thread::spawn")
+    );
+}
+
+#[test]
+fn drd_conflicting_accesses_contain_the_other_segment() {
+    let xml = fs::read("src/valgrind/xml/drd.xml").expect("Could not open test file");
+    let xml = crate::valgrind::parse(&xml).expect("Could not read test file");
+    assert_eq!(xml.tool, super::Tool::Drd);
+
+    let errors = xml.errors.expect("There are errors in the test case");
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].kind, Kind::ConflictingAccess);
+    assert_eq!(errors[0].thread_name.as_deref(), Some("main"));
+    assert_eq!(
+        errors[0].auxiliary_info,
+        [
+            "start of the conflicting segment",
+            "end of the conflicting segment",
+            "Allocation context: BSS section of /project/target/debug/race",
+        ]
+    );
+    assert_eq!(errors[0].stack_trace.len(), 3);
+}
+
+#[test]
+fn file_descriptor_errors_contain_the_descriptor_and_its_stacks() {
+    let xml = fs::read("src/valgrind/xml/fds.xml").expect("Could not open test file");
+    let xml = crate::valgrind::parse(&xml).expect("Could not read test file");
+
+    // the pipe inherited from the parent process is removed
+    let errors = xml.errors.expect("There are errors in the test case");
+    assert_eq!(errors.len(), 3);
+    assert_eq!(errors[0].kind, Kind::FdBadClose);
+    assert_eq!(errors[0].fd, Some(3));
+    assert_eq!(errors[0].path.as_deref(), Some("/tmp/daemon.log"));
+    assert_eq!(
+        errors[0].auxiliary_info,
+        ["Previously closed", "Originally opened"]
+    );
+    assert_eq!(errors[0].stack_trace.len(), 3);
+
+    assert_eq!(errors[1].kind, Kind::FdNotClosed);
+    assert_eq!(errors[1].fd, Some(4));
+    assert_eq!(errors[1].path, None);
+    assert_eq!(
+        errors[1].main_info.as_deref(),
+        Some("Open AF_INET socket 4: 127.0.0.1:41234 <-> 127.0.0.1:8080")
+    );
+    assert_eq!(
+        errors[1].stack_trace[0].frames[2].function.as_deref(),
+        Some("daemon::connect")
+    );
+    assert_eq!(errors[2].path.as_deref(), Some("/project/state.db"));
+}
+
+#[test]
+fn unknown_error_kinds_are_kept() {
+    let error: Error = from_str(
+        "<error>\
+           <unique>0x2</unique>\
+           <tid>1</tid>\
+           <kind>SomeFutureError</kind>\
+           <what>Something went wrong</what>\
+           <auxwhat>Address 0x0 is not stack'd, malloc'd or (recently) free'd</auxwhat>\
+         </error>",
+    )
+    .expect("Could not parse test XML");
+    assert_eq!(error.kind, Kind::Other("SomeFutureError".into()));
+    assert_eq!(error.kind.id(), "SomeFutureError");
+    assert_eq!(error.main_info.as_deref(), Some("Something went wrong"));
+    assert!(error.stack_trace.is_empty());
+
+    let json = serde_json::to_string(&error).expect("Could not serialize error");
+    let error: Error = serde_json::from_str(&json).expect("Could not deserialize error");
+    assert_eq!(error.kind, Kind::Other("SomeFutureError".into()));
+}
+
+#[test]
+fn all_memcheck_error_kinds_are_known() {
+    let kinds = [
+        ("CoreMemError", Kind::CoreMem),
+        ("FishyValue", Kind::FishyValue),
+        ("ReallocSizeZero", Kind::ReallocSizeZero),
+        ("InvalidAlignment", Kind::InvalidAlignment),
+        ("InvalidSize", Kind::InvalidSize),
+        ("MismatchedAllocateDeallocateSize", Kind::MismatchedSize),
+        (
+            "MismatchedAllocateDeallocateAlignment",
+            Kind::MismatchedAlignment,
+        ),
+    ];
+    for (name, kind) in kinds {
+        let parsed: Kind = serde_json::from_value(name.into()).expect("Could not parse kind");
+        assert_eq!(parsed, kind);
+        assert_eq!(parsed.id(), name);
+    }
+}
+
+#[test]
+fn newer_protocol_versions_are_parsed_on_a_best_effort_basis() {
+    let xml =
+        fs::read_to_string("src/valgrind/xml/version6.xml").expect("Could not open test file");
+    let newer = xml.replace(
+        "<protocolversion>6</protocolversion>",
+        "<protocolversion>7</protocolversion>",
+    );
+    let output: Output = from_str(&newer).expect("Could not read test file");
+    assert_eq!(output.newer_protocol_version(), Some(7));
+
+    let older = xml.replace(
+        "<protocolversion>6</protocolversion>",
+        "<protocolversion>3</protocolversion>",
+    );
+    assert!(from_str::<Output>(&older).is_err());
+}
+
+#[test]
+fn child_processes_are_described_by_their_command_line() {
+    let xml = fs::read("src/valgrind/xml/version4.xml").expect("Could not open test file");
+    let output = crate::valgrind::parse(&xml).expect("Could not read test file");
+
+    assert_eq!(
+        super::Process::of(&output),
+        super::Process {
+            pid: Some(17670),
+            ppid: Some(9061),
+            command: "target/debug/examples/creation".into(),
+        }
+    );
+}
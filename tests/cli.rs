@@ -44,3 +44,203 @@ fn help_is_supported() {
         .success()
         .stdout(predicates::str::contains("cargo valgrind"));
 }
+
+#[test]
+fn json_report_is_printed_to_stdout() {
+    cargo_valgrind()
+        .arg("--message-format=json")
+        .arg("run")
+        .args(TARGET_CRATE)
+        .assert()
+        .failure()
+        .stdout(predicates::str::contains(r#""kind":"Leak_DefinitelyLost""#))
+        .stdout(predicates::function::function(|stdout: &str| {
+            // the report follows the output of the program
+            stdout
+                .lines()
+                .last()
+                .is_some_and(|line| line.starts_with(r#"{"reason":"valgrind-report""#))
+        }))
+        .stderr(predicates::str::contains("Summary Leaked"));
+}
+
+#[test]
+fn invalid_options_are_reported() {
+    cargo_valgrind()
+        .args(["--message-format", "yaml", "run"])
+        .args(TARGET_CRATE)
+        .assert()
+        .failure()
+        .code(1)
+        .stderr(predicates::str::contains(
+            "invalid value for `--message-format`",
+        ));
+}
+
+#[test]
+fn sarif_report_is_written() {
+    let sarif = std::env::temp_dir().join("cargo-valgrind-cli-test.sarif");
+    cargo_valgrind()
+        .arg("--sarif")
+        .arg(&sarif)
+        .arg("run")
+        .args(TARGET_CRATE)
+        .assert()
+        .failure();
+
+    let contents = std::fs::read_to_string(&sarif).expect("SARIF file is written");
+    std::fs::remove_file(&sarif).ok();
+    assert!(contents.contains(r#""version": "2.1.0""#));
+    assert!(contents.contains(r#""ruleId": "Leak_DefinitelyLost""#));
+}
+
+#[test]
+fn junit_report_is_written() {
+    let junit = std::env::temp_dir().join("cargo-valgrind-cli-test-junit.xml");
+    cargo_valgrind()
+        .arg("--junit")
+        .arg(&junit)
+        .arg("test")
+        .args(TARGET_CRATE)
+        .assert()
+        .success();
+
+    let contents = std::fs::read_to_string(&junit).expect("JUnit file is written");
+    std::fs::remove_file(&junit).ok();
+    assert!(contents.contains("<testsuites"));
+    assert!(contents.contains(r#"failures="0""#));
+}
+
+#[test]
+fn github_annotations_are_anchored_in_the_workspace() {
+    cargo_valgrind()
+        .arg("--github-annotations")
+        .arg("run")
+        .args(TARGET_CRATE)
+        .env_remove("GITHUB_WORKSPACE")
+        .assert()
+        .failure()
+        .stdout(predicates::str::contains(
+            "::error title=Leak (definitely lost),file=src/main.rs,line=9::leaked 5 B in 1 block",
+        ));
+}
+
+#[test]
+fn saved_xml_output_can_be_reported() {
+    cargo_valgrind()
+        .args([
+            "--message-format=json",
+            "report",
+            "src/valgrind/xml/version4.xml",
+        ])
+        .assert()
+        .failure()
+        .code(127)
+        .stdout(predicates::str::contains(
+            r#""binary":"target/debug/examples/creation""#,
+        ))
+        .stderr(predicates::str::contains("Error leaked 15 B in 1 block"));
+}
+
+#[test]
+fn leaks_are_written_as_folded_stacks() {
+    let folded = std::env::temp_dir().join("cargo-valgrind-cli-test-leaks.folded");
+    cargo_valgrind()
+        .arg("--folded")
+        .arg(&folded)
+        .args(["report", "src/valgrind/xml/version4.xml"])
+        .assert()
+        .failure()
+        .code(127);
+
+    let contents = std::fs::read_to_string(&folded).expect("folded stacks are written");
+    std::fs::remove_file(&folded).ok();
+    assert!(contents.lines().any(|line| line.ends_with(";malloc 72704")));
+}
+
+#[test]
+fn saved_xml_output_without_errors_is_successful() {
+    cargo_valgrind()
+        .args(["report", "src/valgrind/xml/version6.xml"])
+        .assert()
+        .success();
+}
+
+#[test]
+fn error_exitcode_in_valgrindflags_is_ignored() {
+    cargo_valgrind()
+        .env("VALGRINDFLAGS", "--error-exitcode=3")
+        .args(["report", "src/valgrind/xml/version6.xml"])
+        .assert()
+        .success()
+        .stderr(predicates::str::contains(
+            "`--error-exitcode=3` in VALGRINDFLAGS is ignored",
+        ));
+}
+
+#[test]
+fn known_errors_are_ignored_with_a_baseline() {
+    let baseline = std::env::temp_dir().join("cargo-valgrind-cli-test.baseline");
+    let baseline_arg = format!("--baseline={}", baseline.display());
+    cargo_valgrind()
+        .args([&baseline_arg, "--write-baseline", "report"])
+        .arg("src/valgrind/xml/version4.xml")
+        .assert()
+        .success();
+
+    cargo_valgrind()
+        .args([&baseline_arg, "report", "src/valgrind/xml/version4.xml"])
+        .assert()
+        .success()
+        .stderr(predicates::str::contains(
+            "8 known errors from the baseline were ignored",
+        ));
+
+    cargo_valgrind()
+        .args([&baseline_arg, "report", "src/valgrind/xml/version6.xml"])
+        .assert()
+        .success()
+        .stderr(predicates::str::contains("Fixed"));
+    std::fs::remove_file(baseline).ok();
+}
+
+#[test]
+fn missing_baseline_is_reported() {
+    cargo_valgrind()
+        .args(["--baseline=does-not-exist.baseline", "report"])
+        .arg("src/valgrind/xml/version4.xml")
+        .assert()
+        .failure()
+        .code(1)
+        .stderr(predicates::str::contains("--write-baseline"));
+}
+
+#[test]
+fn raw_output_is_archived_in_target_directory() {
+    cargo_valgrind()
+        .arg("run")
+        .args(TARGET_CRATE)
+        .assert()
+        .failure();
+
+    let index = std::fs::read_to_string("tests/ffi-bug/target/valgrind/index.jsonl")
+        .expect("index file is written");
+    let entry: serde_json::Value =
+        serde_json::from_str(index.lines().next().expect("run is listed in index"))
+            .expect("index contains JSON lines");
+    assert_eq!(entry["exit_code"], 127);
+    let directory = std::path::Path::new(entry["directory"].as_str().expect("directory"));
+    assert!(directory.join("valgrind.xml").is_file());
+    assert!(directory.join("stderr.txt").is_file());
+    assert!(directory.join("report.json").is_file());
+}
+
+#[test]
+fn writing_results_requires_a_results_file() {
+    cargo_valgrind()
+        .args(["--tool=callgrind", "--write-results", "bench"])
+        .assert()
+        .failure()
+        .code(1)
+        .stderr(predicates::str::contains("--results <FILE>"));
+}
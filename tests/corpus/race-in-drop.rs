@@ -0,0 +1,24 @@
+//! A data race inside of a `Drop` implementation, which is called by
+//! `Arc::drop_slow()`. This must still be reported by helgrind and DRD, even
+//! though the reference counting of `Arc<T>` itself is suppressed.
+use std::sync::Arc;
+use std::thread;
+
+static mut DROPPED: usize = 0;
+
+struct Counted;
+impl Drop for Counted {
+    fn drop(&mut self) {
+        // unsynchronized read-modify-write from multiple threads
+        unsafe { DROPPED += 1 };
+    }
+}
+
+fn main() {
+    let threads: Vec<_> = (0..2)
+        .map(|_| thread::spawn(|| drop(Arc::new(Counted))))
+        .collect();
+    for thread in threads {
+        thread.join().expect("thread panicked");
+    }
+}
@@ -0,0 +1,3 @@
+fn main() {
+    panic!("the program under test panicked");
+}
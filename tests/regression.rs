@@ -1,4 +1,5 @@
 use assert_cmd::Command;
+use predicates::boolean::PredicateBooleanExt as _;
 
 fn cargo_valgrind() -> Command {
     let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cargo-valgrind");
@@ -130,6 +131,55 @@ fn program_under_test_aborts_with_leaks() {
         .code(128 + 6);
 }
 
+/// The stderr of the program under test (e.g. a panic message) has to be shown
+/// instead of being swallowed.
+#[test]
+fn stderr_of_program_under_test_is_forwarded() {
+    const FFI_TARGET_CRATE: &[&str] = &["--manifest-path", "tests/program-aborts/Cargo.toml"];
+    cargo_valgrind()
+        .arg("run")
+        .args(FFI_TARGET_CRATE)
+        .arg("--bin=panics")
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("the program under test panicked"));
+}
+
+/// A failing program (e.g. a failing test) is no invalid usage of valgrind and
+/// its exit code has to be forwarded.
+#[test]
+fn exit_code_of_program_under_test_is_forwarded() {
+    const FFI_TARGET_CRATE: &[&str] = &["--manifest-path", "tests/program-aborts/Cargo.toml"];
+    cargo_valgrind()
+        .arg("run")
+        .args(FFI_TARGET_CRATE)
+        .arg("--bin=panics")
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains(
+            "info: the program exited with code 101",
+        ))
+        .stderr(predicates::str::contains("invalid valgrind usage").not())
+        .code(101);
+}
+
+/// The suppressions of the `Arc<T>` reference counting must not hide a real data
+/// race inside of the `Drop` implementation of the shared value.
+#[test]
+fn race_in_drop_of_arc_value_is_reported() {
+    for tool in ["--tool=helgrind", "--tool=drd"] {
+        cargo_valgrind()
+            .arg(tool)
+            .arg("run")
+            .args(TARGET_CRATE)
+            .arg("--bin=race-in-drop")
+            .assert()
+            .failure()
+            .stderr(predicates::str::contains("race-in-drop.rs:13"))
+            .code(127);
+    }
+}
+
 /// If a program crashes within running it in Valgrind, a `vgcore.<pid>`-file
 /// might be created in the current working directory. In order to not clutter
 /// the main project directory, this type can be used as a drop-guard to delete